@@ -0,0 +1,303 @@
+// [[file:../enm.note::b6f0c28e][b6f0c28e]]
+use gut::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::modes::{NetworkModel, NormalModes};
+use crate::pdb::AtomRecord;
+
+/// Checksum of Cartesian coordinates for detecting stale mode caches,
+/// computed by 64-bit FNV-1a hash over the little-endian bytes of all
+/// coordinates.
+pub fn coordinates_checksum(coords: &[[f64; 3]]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in coords.iter().flatten().flat_map(|x| x.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Normal modes saved together with model parameters, atom metadata
+/// and checksum of the input coordinates.
+///
+/// Two formats are supported:
+///
+/// - JSON, for small systems.
+/// - Binary, with all integers and floats in little-endian:
+///
+/// | field        | type            | description                           |
+/// |--------------|-----------------|---------------------------------------|
+/// | magic        | 8 bytes         | `b"ENMMODES"`                         |
+/// | version      | u32             | format version, currently 2           |
+/// | header size  | u64             | size in bytes of the JSON header      |
+/// | header       | UTF-8 JSON      | model, masses and atoms               |
+/// | natoms       | u64             | the number of atoms                   |
+/// | dim          | u64             | size of each eigenvector              |
+/// | nmodes       | u64             | the number of modes                   |
+/// | checksum     | u64             | see `coordinates_checksum`            |
+/// | eigenvalues  | f64 × nmodes    |                                       |
+/// | eigenvectors | f64 × dim × nmodes | one mode after another             |
+#[derive(Debug, Clone)]
+pub struct ModesArchive {
+    pub modes: NormalModes,
+    /// Checksum of the coordinates used for computing the modes
+    pub checksum: u64,
+    /// Atom records of the structure, if available
+    pub atoms: Option<Vec<AtomRecord>>,
+}
+
+/// Layout of the JSON file.
+#[derive(Serialize, Deserialize)]
+struct JsonArchive {
+    version: u32,
+    model: NetworkModel,
+    natoms: usize,
+    masses: Option<Vec<f64>>,
+    atoms: Option<Vec<AtomRecord>>,
+    checksum: u64,
+    eigenvalues: Vec<f64>,
+    /// one eigenvector per mode
+    eigenvectors: Vec<Vec<f64>>,
+}
+
+/// JSON header in the binary file.
+#[derive(Serialize, Deserialize)]
+struct BinaryHeader {
+    model: NetworkModel,
+    masses: Option<Vec<f64>>,
+    atoms: Option<Vec<AtomRecord>>,
+}
+
+const MAGIC: &[u8; 8] = b"ENMMODES";
+/// Version 2 writes spring types in snake_case. Version 1 files with
+/// CamelCase names are still readable.
+const VERSION: u32 = 2;
+
+impl ModesArchive {
+    /// Construct from `modes` computed for `coords`.
+    pub fn new(modes: NormalModes, coords: &[[f64; 3]], atoms: Option<Vec<AtomRecord>>) -> Self {
+        assert_eq!(coords.len(), modes.natoms(), "invalid number of atoms");
+        if let Some(atoms) = &atoms {
+            assert_eq!(atoms.len(), modes.natoms(), "invalid number of atom records");
+        }
+        Self {
+            modes,
+            checksum: coordinates_checksum(coords),
+            atoms,
+        }
+    }
+
+    /// Returns true if the modes were computed for `coords`.
+    pub fn matches(&self, coords: &[[f64; 3]]) -> bool {
+        coords.len() == self.modes.natoms() && coordinates_checksum(coords) == self.checksum
+    }
+
+    /// Returns the modes if they were computed for `coords`, or an
+    /// error for stale modes.
+    pub fn into_modes_for(self, coords: &[[f64; 3]]) -> Result<NormalModes> {
+        ensure!(self.matches(coords), "saved modes were computed for different coordinates");
+        Ok(self.modes)
+    }
+
+    /// Serialize in JSON format.
+    pub fn to_json(&self) -> Result<String> {
+        let modes = &self.modes;
+        let archive = JsonArchive {
+            version: VERSION,
+            model: modes.model().clone(),
+            natoms: modes.natoms(),
+            masses: modes.masses().map(|m| m.to_vec()),
+            atoms: self.atoms.clone(),
+            checksum: self.checksum,
+            eigenvalues: modes.eigenvalues().to_vec(),
+            eigenvectors: modes.iter().map(|(_, v)| v.to_vec()).collect(),
+        };
+        serde_json::to_string(&archive).context("failed to serialize modes")
+    }
+
+    /// Deserialize from JSON format.
+    pub fn from_json(s: &str) -> Result<Self> {
+        let archive: JsonArchive = serde_json::from_str(s).context("invalid JSON for modes")?;
+        ensure!((1..=VERSION).contains(&archive.version), "unsupported version: {}", archive.version);
+        ensure!(archive.eigenvalues.len() == archive.eigenvectors.len(), "inconsistent number of modes");
+        let modes = archive.eigenvalues.into_iter().zip(archive.eigenvectors).collect();
+        let modes = new_modes(archive.model, archive.natoms, archive.masses, modes)?;
+        Ok(Self {
+            modes,
+            checksum: archive.checksum,
+            atoms: archive.atoms,
+        })
+    }
+
+    /// Serialize in binary format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let modes = &self.modes;
+        let header = BinaryHeader {
+            model: modes.model().clone(),
+            masses: modes.masses().map(|m| m.to_vec()),
+            atoms: self.atoms.clone(),
+        };
+        let header = serde_json::to_vec(&header).context("failed to serialize header")?;
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend((header.len() as u64).to_le_bytes());
+        bytes.extend(header);
+        for n in [modes.natoms(), modes.eigenvectors().nrows(), modes.nmodes()] {
+            bytes.extend((n as u64).to_le_bytes());
+        }
+        bytes.extend(self.checksum.to_le_bytes());
+        for x in modes.eigenvalues().iter().chain(modes.eigenvectors().iter()) {
+            bytes.extend(x.to_le_bytes());
+        }
+        Ok(bytes)
+    }
+
+    /// Deserialize from binary format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader { bytes, offset: 0 };
+        ensure!(reader.take(8)? == MAGIC, "not a binary modes file");
+        let version = u32::from_le_bytes(reader.take(4)?.try_into()?);
+        ensure!((1..=VERSION).contains(&version), "unsupported version: {version}");
+        let n = reader.read_u64()? as usize;
+        let header: BinaryHeader = serde_json::from_slice(reader.take(n)?).context("invalid header")?;
+        let natoms = reader.read_u64()? as usize;
+        let dim = reader.read_u64()? as usize;
+        let nmodes = reader.read_u64()? as usize;
+        let checksum = reader.read_u64()?;
+        let eigenvalues = reader.read_f64s(nmodes)?;
+        let modes = eigenvalues
+            .into_iter()
+            .map(|value| Ok((value, reader.read_f64s(dim)?)))
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(reader.offset == bytes.len(), "trailing bytes in modes file");
+        let modes = new_modes(header.model, natoms, header.masses, modes)?;
+        Ok(Self {
+            modes,
+            checksum,
+            atoms: header.atoms,
+        })
+    }
+
+    /// Save to file at `path`, in JSON format if the extension is
+    /// `json`, or in binary format otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let bytes = if is_json(path) { self.to_json()?.into_bytes() } else { self.to_bytes()? };
+        std::fs::write(path, bytes).with_context(|| format!("failed to write {path:?}"))
+    }
+
+    /// Load from file at `path` saved by `save`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("failed to read {path:?}"))?;
+        let archive = if is_json(path) {
+            Self::from_json(std::str::from_utf8(&bytes)?)
+        } else {
+            Self::from_bytes(&bytes)
+        };
+        archive.with_context(|| format!("failed to load modes from {path:?}"))
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().map(|x| x.eq_ignore_ascii_case("json")).unwrap_or(false)
+}
+
+/// Validate sizes before constructing `NormalModes`, which panics on
+/// invalid input.
+fn new_modes(model: NetworkModel, natoms: usize, masses: Option<Vec<f64>>, modes: Vec<(f64, Vec<f64>)>) -> Result<NormalModes> {
+    let dim = match model {
+        NetworkModel::Anisotropic(_) => 3 * natoms,
+        NetworkModel::Gaussian(_) => natoms,
+    };
+    ensure!(modes.iter().all(|(_, v)| v.len() == dim), "invalid eigenvector size");
+    if let Some(masses) = &masses {
+        ensure!(masses.len() == natoms, "invalid number of masses");
+    }
+    Ok(NormalModes::new(model, natoms, masses, modes))
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.offset.checked_add(n).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or_else(|| anyhow!("unexpected end of modes file"))?;
+        let s = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(s)
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn read_f64s(&mut self, n: usize) -> Result<Vec<f64>> {
+        let bytes = self.take(n.checked_mul(8).ok_or_else(|| anyhow!("invalid size"))?)?;
+        Ok(bytes.chunks_exact(8).map(|b| f64::from_le_bytes(b.try_into().unwrap())).collect())
+    }
+}
+
+#[test]
+fn test_modes_archive() -> Result<()> {
+    use crate::{AnisotropicNetworkModel, GaussianNetworkModel, SpringConstant};
+
+    let coords = crate::enm::test_coords();
+
+    let anm = AnisotropicNetworkModel {
+        mass_weighted: true,
+        spring: SpringConstant::Exponential(5.0),
+        ..Default::default()
+    };
+    let modes = anm.normal_modes(&coords, None);
+    let archive = ModesArchive::new(modes.clone(), &coords, None);
+    assert!(archive.matches(&coords));
+    let mut moved = coords;
+    moved[0][0] += 1E-6;
+    assert!(!archive.matches(&moved));
+
+    // binary and JSON round trips
+    let dir = std::env::temp_dir();
+    for name in ["enm-test-modes.bin", "enm-test-modes.json"] {
+        let path = dir.join(name);
+        archive.save(&path)?;
+        let loaded = ModesArchive::load(&path)?;
+        std::fs::remove_file(&path)?;
+        assert!(loaded.clone().into_modes_for(&moved).is_err());
+        let loaded = loaded.into_modes_for(&coords)?;
+        assert_eq!(loaded.eigenvalues(), modes.eigenvalues());
+        assert_eq!(loaded.eigenvectors(), modes.eigenvectors());
+        assert_eq!(loaded.masses(), modes.masses());
+        match loaded.model() {
+            NetworkModel::Anisotropic(m) => assert!(m.mass_weighted && matches!(m.spring, SpringConstant::Exponential(r) if r == 5.0)),
+            _ => panic!("wrong model"),
+        }
+    }
+
+    // version 1 files with CamelCase spring names
+    let json = archive.to_json()?;
+    let json = json.replace("\"version\":2", "\"version\":1").replace("\"exponential\"", "\"Exponential\"");
+    assert!(json.contains("\"Exponential\""));
+    let loaded = ModesArchive::from_json(&json)?;
+    assert!(matches!(loaded.modes.model(), NetworkModel::Anisotropic(m) if matches!(m.spring, SpringConstant::Exponential(_))));
+
+    let gnm = GaussianNetworkModel::default();
+    let archive = ModesArchive::new(gnm.normal_modes(&coords), &coords, None);
+    let bytes = archive.to_bytes()?;
+    assert_eq!(ModesArchive::from_bytes(&bytes)?.modes.dof_per_atom(), 1);
+    assert!(ModesArchive::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+    // custom springs cannot be saved
+    let anm = AnisotropicNetworkModel {
+        spring: SpringConstant::custom(|_, _, _| 1.0),
+        ..Default::default()
+    };
+    let archive = ModesArchive::new(anm.normal_modes(&coords, None), &coords, None);
+    assert!(archive.to_json().is_err());
+
+    Ok(())
+}
+// b6f0c28e ends here
@@ -0,0 +1,104 @@
+// [[file:../enm.note::9b2e4d71][9b2e4d71]]
+use crate::NormalModes;
+
+impl NormalModes {
+    /// Mean-square fluctuations <ΔR²> of each atom in units of kT,
+    /// summed over the first `nmodes` modes (all modes if None).
+    pub fn mean_square_fluctuations(&self, nmodes: impl Into<Option<usize>>) -> Vec<f64> {
+        let nmodes = nmodes.into().unwrap_or(self.nmodes()).min(self.nmodes());
+        let mut msf = vec![0.0; self.natoms()];
+        for k in 0..nmodes {
+            let (value, vector) = self.mode(k);
+            if self.dof_per_atom() == 3 {
+                for (i, d) in self.displacements(k).iter().enumerate() {
+                    msf[i] += (d[0].powi(2) + d[1].powi(2) + d[2].powi(2)) / value;
+                }
+            } else {
+                // isotropic fluctuations in GNM: 3 kT [Γ^-1]_ii
+                for (i, u) in vector.iter().enumerate() {
+                    msf[i] += 3.0 * u.powi(2) / value;
+                }
+            }
+        }
+        msf
+    }
+
+    /// Theoretical B-factors (8π²/3 <ΔR²>) of each atom from the first
+    /// `nmodes` modes (all modes if None), in units of kT.
+    pub fn bfactors(&self, nmodes: impl Into<Option<usize>>) -> Vec<f64> {
+        let factor = 8.0 * std::f64::consts::PI.powi(2) / 3.0;
+        self.mean_square_fluctuations(nmodes).into_iter().map(|x| x * factor).collect()
+    }
+}
+
+/// Theoretical B-factors fitted against experimental ones.
+#[derive(Debug, Clone)]
+pub struct BFactorFit {
+    /// Least-squares scaling factor applied to theoretical B-factors
+    pub scale: f64,
+    /// Pearson correlation coefficient between theoretical and
+    /// experimental B-factors
+    pub correlation: f64,
+    /// Scaled theoretical B-factors
+    pub fitted: Vec<f64>,
+}
+
+/// Scale `predicted` B-factors to `experimental` ones by least squares
+/// and compute their Pearson correlation coefficient.
+pub fn fit_bfactors(predicted: &[f64], experimental: &[f64]) -> BFactorFit {
+    assert_eq!(predicted.len(), experimental.len(), "B-factors size mismatch");
+    let n = predicted.len() as f64;
+
+    // minimize |s * predicted - experimental|^2
+    let pp: f64 = predicted.iter().map(|x| x * x).sum();
+    let pe: f64 = predicted.iter().zip(experimental).map(|(x, y)| x * y).sum();
+    let scale = pe / pp;
+    let fitted = predicted.iter().map(|x| x * scale).collect();
+
+    let mean_p = predicted.iter().sum::<f64>() / n;
+    let mean_e = experimental.iter().sum::<f64>() / n;
+    let cov: f64 = predicted.iter().zip(experimental).map(|(x, y)| (x - mean_p) * (y - mean_e)).sum();
+    let var_p: f64 = predicted.iter().map(|x| (x - mean_p).powi(2)).sum();
+    let var_e: f64 = experimental.iter().map(|y| (y - mean_e).powi(2)).sum();
+    let correlation = cov / (var_p * var_e).sqrt();
+
+    BFactorFit {
+        scale,
+        correlation,
+        fitted,
+    }
+}
+
+#[test]
+fn test_bfactors() {
+    use crate::{AnisotropicNetworkModel, GaussianNetworkModel};
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    // fluctuations equal to the trace of diagonal blocks of pseudo-inverse Hessian
+    let anm = AnisotropicNetworkModel::default();
+    let modes = anm.normal_modes(&coords, None);
+    let msf = modes.mean_square_fluctuations(None);
+    let hessian = anm.build_hessian_matrix(&coords, None);
+    let pinv = hessian.pseudo_inverse(1E-6).unwrap();
+    for (i, x) in msf.iter().enumerate() {
+        let trace = pinv.fixed_slice::<3, 3>(3 * i, 3 * i).trace();
+        assert_relative_eq!(*x, trace, epsilon = 1E-6);
+    }
+    // fewer modes give smaller fluctuations
+    let msf3 = modes.mean_square_fluctuations(3);
+    assert!(msf3.iter().zip(&msf).all(|(a, b)| a < b));
+
+    let gnm = GaussianNetworkModel {
+        cutoff: 2.5,
+        ..Default::default()
+    };
+    let modes = gnm.normal_modes(&coords);
+    let bfactors = modes.bfactors(None);
+    let fit = fit_bfactors(&bfactors, &bfactors.iter().map(|x| 2.0 * x).collect::<Vec<_>>());
+    assert_relative_eq!(fit.scale, 2.0, epsilon = 1E-10);
+    assert_relative_eq!(fit.correlation, 1.0, epsilon = 1E-10);
+    assert_relative_eq!(fit.fitted[0], 2.0 * bfactors[0], epsilon = 1E-10);
+}
+// 9b2e4d71 ends here
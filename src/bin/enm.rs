@@ -0,0 +1,313 @@
+// [[file:../../enm.note::4a7c1f93][4a7c1f93]]
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use gut::prelude::*;
+
+use elastic_network_model::*;
+
+/// Elastic network model analysis of biomolecular structures
+#[derive(Parser, Debug)]
+#[command(name = "enm", version)]
+struct Cli {
+    /// Show more log messages: -v for info, -vv for debug, -vvv for
+    /// trace. Warnings are always shown.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Set up logger at level from `verbose` count, which can be overridden
+/// by `RUST_LOG` environment variable.
+fn setup_logger(verbose: u8) {
+    let level = match verbose {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    env_logger::Builder::new().filter_level(level).parse_default_env().init();
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print eigenvalues of ANM modes, optionally saved in NMD or modes
+    /// archive file.
+    Anm {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        model: ModelArgs,
+        /// Write modes in NMD format for VMD NMWiz
+        #[arg(long)]
+        nmd: Option<PathBuf>,
+        /// Save modes in binary or JSON (by extension) archive
+        #[arg(long)]
+        save: Option<PathBuf>,
+    },
+    /// Print eigenvalues of GNM modes.
+    Gnm {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        model: ModelArgs,
+    },
+    /// Print predicted and experimental B-factors per atom.
+    Bfactors {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        model: ModelArgs,
+        /// Use GNM instead of ANM
+        #[arg(long)]
+        gnm: bool,
+    },
+    /// Print Cartesian displacements of atoms along an ANM mode.
+    Modes {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        model: ModelArgs,
+        /// 0-based index of the mode, excluding zero modes
+        #[arg(short, long, default_value_t = 0)]
+        mode: usize,
+    },
+    /// Write trajectory of oscillation along an ANM mode as multi-model
+    /// PDB or XYZ (by extension).
+    Animate {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        model: ModelArgs,
+        /// 0-based index of the mode, excluding zero modes
+        #[arg(short, long, default_value_t = 0)]
+        mode: usize,
+        /// RMSD in Å of the most displaced frame
+        #[arg(long, default_value_t = 2.0)]
+        rmsd: f64,
+        /// The number of frames
+        #[arg(long, default_value_t = 20)]
+        frames: usize,
+        /// Output PDB or XYZ file
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Print overlaps of the conformational change to a target structure
+    /// with ANM modes.
+    Compare {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Target structure with the same atoms as the input
+        target: PathBuf,
+        #[command(flatten)]
+        model: ModelArgs,
+    },
+}
+
+#[derive(Args, Debug)]
+struct InputArgs {
+    /// Input structure in PDB, mmCIF or XYZ format
+    input: PathBuf,
+    /// Atoms to be selected from PDB or mmCIF input
+    #[arg(long, value_enum, default_value_t = AtomKind::Ca)]
+    atoms: AtomKind,
+    /// Select only atoms in these chains
+    #[arg(long, value_delimiter = ',')]
+    chains: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct ModelArgs {
+    /// Cutoff distance in Å, 15 for ANM and 7.3 for GNM by default
+    #[arg(short, long)]
+    cutoff: Option<f64>,
+    /// Force constant of springs
+    #[arg(short, long, default_value_t = 1.0)]
+    gamma: f64,
+    /// Use mass weighted Hessian matrix
+    #[arg(long)]
+    mass_weighted: bool,
+    /// The number of lowest modes to use
+    #[arg(short, long, default_value_t = 20)]
+    nmodes: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum AtomKind {
+    Ca,
+    Backbone,
+    Heavy,
+    All,
+}
+
+impl InputArgs {
+    fn read(&self, path: &Path) -> Result<Structure> {
+        let structure = Structure::from_file(path)?;
+        let is_xyz = path.extension().map(|x| x.eq_ignore_ascii_case("xyz")).unwrap_or(false);
+        // atoms in XYZ files have no names for selection
+        let atoms = match self.atoms {
+            _ if is_xyz => AtomSelection::All,
+            AtomKind::Ca => AtomSelection::CalphaOnly,
+            AtomKind::Backbone => AtomSelection::Backbone,
+            AtomKind::Heavy => AtomSelection::Heavy,
+            AtomKind::All => AtomSelection::All,
+        };
+        let selection = Selection {
+            atoms,
+            chains: self.chains.clone(),
+            ..Default::default()
+        };
+        let structure = structure.select(&selection);
+        ensure!(structure.natoms() > 0, "no atoms selected in {path:?}");
+        info!("{} atoms selected in {path:?}", structure.natoms());
+        Ok(structure)
+    }
+}
+
+impl ModelArgs {
+    fn anm(&self) -> AnisotropicNetworkModel {
+        let default = AnisotropicNetworkModel::default();
+        AnisotropicNetworkModel {
+            cutoff: self.cutoff.unwrap_or(default.cutoff),
+            gamma: self.gamma,
+            mass_weighted: self.mass_weighted,
+            ..default
+        }
+    }
+
+    fn gnm(&self) -> GaussianNetworkModel {
+        let default = GaussianNetworkModel::default();
+        GaussianNetworkModel {
+            cutoff: self.cutoff.unwrap_or(default.cutoff),
+            gamma: self.gamma,
+            ..default
+        }
+    }
+}
+
+fn anm_modes(structure: &Structure, model: &ModelArgs) -> Result<NormalModes> {
+    let anm = model.anm();
+    let (coords, masses) = (structure.coords(), structure.masses());
+    let modes = if 3 * coords.len() > 3000 {
+        anm.try_lowest_normal_modes(&coords, masses.as_slice(), model.nmodes, &LobpcgOptions::default())?
+    } else {
+        anm.try_normal_modes(&coords, masses.as_slice())?
+    };
+    Ok(modes)
+}
+
+fn print_eigenvalues(modes: &NormalModes, nmodes: usize) {
+    let frequencies = modes.frequencies();
+    println!("{:>6} {:>16} {:>12}", "mode", "eigenvalue", "frequency");
+    for (k, value) in modes.eigenvalues().iter().take(nmodes).enumerate() {
+        match &frequencies {
+            Some(f) => println!("{:>6} {:>16.8} {:>12.4}", k, value, f[k]),
+            None => println!("{:>6} {:>16.8} {:>12}", k, value, "-"),
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    setup_logger(cli.verbose);
+
+    match &cli.command {
+        Command::Anm { input, model, nmd, save } => {
+            let structure = input.read(&input.input)?;
+            let modes = anm_modes(&structure, model)?;
+            print_eigenvalues(&modes, model.nmodes);
+            let coords = structure.coords();
+            if let Some(path) = nmd {
+                modes.write_nmd(path, &coords, Some(structure.atoms()), model.nmodes)?;
+            }
+            if let Some(path) = save {
+                ModesArchive::new(modes, &coords, Some(structure.atoms().to_vec())).save(path)?;
+            }
+        }
+        Command::Gnm { input, model } => {
+            let structure = input.read(&input.input)?;
+            let modes = model.gnm().try_normal_modes(&structure.coords())?;
+            print_eigenvalues(&modes, model.nmodes);
+        }
+        Command::Bfactors { input, model, gnm } => {
+            let structure = input.read(&input.input)?;
+            let modes = if *gnm {
+                model.gnm().try_normal_modes(&structure.coords())?
+            } else {
+                anm_modes(&structure, model)?
+            };
+            let predicted = modes.bfactors(model.nmodes);
+            let fit = fit_bfactors(&predicted, &structure.bfactors());
+            println!("# scale = {:.6}, correlation = {:.4}", fit.scale, fit.correlation);
+            println!("{:>5} {:>6} {:>4} {:>4} {:>10} {:>10}", "chain", "resseq", "res", "atom", "bfactor", "predicted");
+            for (atom, b) in structure.atoms().iter().zip(&fit.fitted) {
+                println!(
+                    "{:>5} {:>6} {:>4} {:>4} {:>10.2} {:>10.2}",
+                    atom.chain, atom.resseq, atom.resname, atom.name, atom.bfactor, b
+                );
+            }
+        }
+        Command::Modes { input, model, mode } => {
+            let structure = input.read(&input.input)?;
+            let modes = anm_modes(&structure, model)?;
+            ensure!(*mode < modes.nmodes(), "mode {mode} is out of range: {} modes", modes.nmodes());
+            println!("# mode {} eigenvalue = {:.8}", mode, modes.eigenvalues()[*mode]);
+            println!("{:>5} {:>6} {:>4} {:>4} {:>10} {:>10} {:>10}", "chain", "resseq", "res", "atom", "dx", "dy", "dz");
+            for (atom, [dx, dy, dz]) in structure.atoms().iter().zip(modes.displacements(*mode)) {
+                println!(
+                    "{:>5} {:>6} {:>4} {:>4} {:>10.5} {:>10.5} {:>10.5}",
+                    atom.chain, atom.resseq, atom.resname, atom.name, dx, dy, dz
+                );
+            }
+        }
+        Command::Animate {
+            input,
+            model,
+            mode,
+            rmsd,
+            frames,
+            output,
+        } => {
+            let structure = input.read(&input.input)?;
+            let modes = anm_modes(&structure, model)?;
+            ensure!(*mode < modes.nmodes(), "mode {mode} is out of range: {} modes", modes.nmodes());
+            let traj = modes.mode_trajectory(&structure.coords(), *mode, Amplitude::Rmsd(*rmsd), *frames);
+            if output.extension().map(|x| x.eq_ignore_ascii_case("xyz")).unwrap_or(false) {
+                let elements = structure.atoms().iter().map(|a| a.element.clone()).collect_vec();
+                traj.write_xyz(output, Some(&elements))?;
+            } else {
+                traj.write_pdb(output, Some(structure.atoms()))?;
+            }
+        }
+        Command::Compare { input, target, model } => {
+            let start = input.read(&input.input)?;
+            let target = input.read(target)?;
+            ensure!(
+                start.natoms() == target.natoms(),
+                "different number of atoms selected: {} != {}",
+                start.natoms(),
+                target.natoms()
+            );
+            let (coords, masses) = (start.coords(), start.masses());
+            let modes = anm_modes(&start, model)?;
+            let weights = model.mass_weighted.then_some(masses.as_slice());
+            let deformation = deformation_vector(&coords, &target.coords(), weights);
+            let rmsd = superpose(&target.coords(), &coords, weights).rmsd;
+            let result = modes.deformation_overlap(&deformation);
+            println!("# RMSD = {rmsd:.4}");
+            println!("{:>6} {:>16} {:>10} {:>10}", "mode", "eigenvalue", "overlap", "cumulative");
+            for k in 0..model.nmodes.min(modes.nmodes()) {
+                println!(
+                    "{:>6} {:>16.8} {:>10.4} {:>10.4}",
+                    k,
+                    modes.eigenvalues()[k],
+                    result.overlaps[k],
+                    result.cumulative[k]
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+// 4a7c1f93 ends here
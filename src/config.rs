@@ -0,0 +1,274 @@
+// [[file:../enm.note::c3d95a17][c3d95a17]]
+use gut::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{validate_gamma, validate_springs};
+use crate::pdb::{AtomSelection, Selection, Structure};
+use crate::spring::{BondedSprings, SpringConstant};
+use crate::{AnisotropicNetworkModel, GaussianNetworkModel};
+
+/// Type of elastic network model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelKind {
+    #[default]
+    Anm,
+    Gnm,
+}
+
+/// Atom selection in configuration file. See `Selection` for details.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SelectionConfig {
+    pub atoms: AtomSelection,
+    pub chains: Vec<String>,
+    /// First and last residue sequence numbers
+    pub residues: Option<[i32; 2]>,
+    pub altloc: Option<char>,
+    pub model: usize,
+    pub hetero: bool,
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        let selection = Selection::default();
+        Self {
+            atoms: selection.atoms,
+            chains: selection.chains,
+            residues: None,
+            altloc: selection.altloc,
+            model: selection.model,
+            hetero: selection.hetero,
+        }
+    }
+}
+
+/// Model settings read from TOML or JSON configuration, for example:
+///
+/// ```toml
+/// model = "anm"
+/// cutoff = 12.0
+/// gamma = 1.0
+/// spring = { inverse_power = 2.0 }
+/// bonded_gammas = [10.0, 5.0]
+/// mass_weighted = true
+/// nmodes = 20
+///
+/// [selection]
+/// atoms = "backbone"
+/// chains = ["A"]
+/// residues = [1, 100]
+/// ```
+///
+/// Parameters not given take the defaults of the model.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    pub model: ModelKind,
+    pub cutoff: Option<f64>,
+    pub gamma: Option<f64>,
+    /// Distance dependence of spring force constants
+    pub spring: SpringConstant,
+    /// Force constants for bonded neighbors i±1, i±2, ... along chains
+    /// of the selected structure. Only for ANM
+    pub bonded_gammas: Vec<f64>,
+    /// Only for ANM
+    pub mass_weighted: bool,
+    pub zero_tolerance: Option<f64>,
+    pub selection: SelectionConfig,
+    /// The number of lowest modes to compute or use, all if None
+    pub nmodes: Option<usize>,
+}
+
+impl ModelConfig {
+    /// Parse configuration in TOML format.
+    pub fn from_toml_str(s: &str) -> Result<Self> {
+        let config: Self = toml::from_str(s).context("invalid TOML configuration")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse configuration in JSON format.
+    pub fn from_json_str(s: &str) -> Result<Self> {
+        let config: Self = serde_json::from_str(s).context("invalid JSON configuration")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read configuration from file at `path`, in JSON format if the
+    /// extension is `json`, or in TOML format otherwise.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+        let is_json = path.extension().map(|x| x.eq_ignore_ascii_case("json")).unwrap_or(false);
+        let config = if is_json { Self::from_json_str(&s) } else { Self::from_toml_str(&s) };
+        config.with_context(|| format!("invalid configuration in {path:?}"))
+    }
+
+    /// Format configuration in TOML format.
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string(self).context("failed to serialize configuration")
+    }
+
+    /// Check parameters for physically meaningful values.
+    pub fn validate(&self) -> Result<()> {
+        let (cutoff, gamma, zero_tolerance) = match self.model {
+            ModelKind::Anm => {
+                let default = AnisotropicNetworkModel::default();
+                (default.cutoff, default.gamma, default.zero_tolerance)
+            }
+            ModelKind::Gnm => {
+                let default = GaussianNetworkModel::default();
+                (default.cutoff, default.gamma, default.zero_tolerance)
+            }
+        };
+        validate_springs(
+            self.cutoff.unwrap_or(cutoff),
+            self.gamma.unwrap_or(gamma),
+            &self.spring,
+            self.zero_tolerance.unwrap_or(zero_tolerance),
+        )?;
+        for &gamma in &self.bonded_gammas {
+            validate_gamma(gamma).context("invalid bonded_gammas")?;
+        }
+        ensure!(
+            !(self.model == ModelKind::Gnm && self.mass_weighted),
+            "mass_weighted is only available for ANM"
+        );
+        ensure!(
+            self.model == ModelKind::Anm || self.bonded_gammas.is_empty(),
+            "bonded_gammas is only available for ANM"
+        );
+        ensure!(self.nmodes != Some(0), "nmodes must be positive");
+        let selection = &self.selection;
+        ensure!(selection.model > 0, "selection.model is 1-based, got 0");
+        if let Some([first, last]) = selection.residues {
+            ensure!(first <= last, "invalid selection.residues range: [{first}, {last}]");
+        }
+        Ok(())
+    }
+
+    /// Construct ANM from the configuration. Bonded springs require the
+    /// topology of atoms; see `anm_for`.
+    pub fn anm(&self) -> Result<AnisotropicNetworkModel> {
+        ensure!(self.bonded_gammas.is_empty(), "bonded_gammas requires the structure of atoms");
+        self.build_anm()
+    }
+
+    /// Construct ANM from the configuration for atoms in `structure`
+    /// selected by `selection`, with bonded springs along its chains.
+    pub fn anm_for(&self, structure: &Structure) -> Result<AnisotropicNetworkModel> {
+        let mut anm = self.build_anm()?;
+        if !self.bonded_gammas.is_empty() {
+            anm.bonded = Some(BondedSprings {
+                topology: structure.topology(),
+                gammas: self.bonded_gammas.clone(),
+            });
+        }
+        Ok(anm)
+    }
+
+    fn build_anm(&self) -> Result<AnisotropicNetworkModel> {
+        self.validate()?;
+        ensure!(self.model == ModelKind::Anm, "configuration is not for ANM");
+        let default = AnisotropicNetworkModel::default();
+        Ok(AnisotropicNetworkModel {
+            cutoff: self.cutoff.unwrap_or(default.cutoff),
+            gamma: self.gamma.unwrap_or(default.gamma),
+            spring: self.spring.clone(),
+            mass_weighted: self.mass_weighted,
+            zero_tolerance: self.zero_tolerance.unwrap_or(default.zero_tolerance),
+            ..default
+        })
+    }
+
+    /// Construct GNM from the configuration.
+    pub fn gnm(&self) -> Result<GaussianNetworkModel> {
+        self.validate()?;
+        ensure!(self.model == ModelKind::Gnm, "configuration is not for GNM");
+        let default = GaussianNetworkModel::default();
+        Ok(GaussianNetworkModel {
+            cutoff: self.cutoff.unwrap_or(default.cutoff),
+            gamma: self.gamma.unwrap_or(default.gamma),
+            spring: self.spring.clone(),
+            zero_tolerance: self.zero_tolerance.unwrap_or(default.zero_tolerance),
+        })
+    }
+
+    /// Atom selection from the configuration.
+    pub fn selection(&self) -> Selection {
+        let s = &self.selection;
+        Selection {
+            atoms: s.atoms,
+            chains: s.chains.clone(),
+            residues: s.residues.map(|[first, last]| first..=last),
+            altloc: s.altloc,
+            model: s.model,
+            hetero: s.hetero,
+        }
+    }
+}
+
+#[test]
+fn test_model_config() -> Result<()> {
+    let config = ModelConfig::from_toml_str(
+        r#"
+cutoff = 12.0
+spring = { inverse_power = 2.0 }
+mass_weighted = true
+nmodes = 20
+
+[selection]
+atoms = "backbone"
+chains = ["A"]
+residues = [1, 100]
+"#,
+    )?;
+    let anm = config.anm()?;
+    assert_eq!(anm.cutoff, 12.0);
+    assert_eq!(anm.gamma, 1.0);
+    assert!(anm.mass_weighted);
+    assert!(matches!(anm.spring, SpringConstant::InversePower(p) if p == 2.0));
+    assert!(config.gnm().is_err());
+    let selection = config.selection();
+    assert_eq!(selection.atoms, AtomSelection::Backbone);
+    assert_eq!(selection.residues, Some(1..=100));
+    assert_eq!(selection.altloc, None);
+
+    // round trip
+    let config = ModelConfig::from_toml_str(&config.to_toml_string()?)?;
+    assert_eq!(config.nmodes, Some(20));
+
+    let config = ModelConfig::from_json_str(r#"{"model": "gnm", "selection": {"atoms": "ca"}}"#)?;
+    assert_eq!(config.gnm()?.cutoff, 7.3);
+
+    // invalid settings
+    assert!(ModelConfig::from_toml_str("cutoff = -1.0").is_err());
+    assert!(ModelConfig::from_toml_str("cutof = 10.0").is_err());
+    assert!(ModelConfig::from_toml_str("model = \"gnm\"\nmass_weighted = true").is_err());
+    assert!(ModelConfig::from_toml_str("spring = { exponential = 0.0 }").is_err());
+    assert!(ModelConfig::from_toml_str("[selection]\nresidues = [10, 1]").is_err());
+    let e = ModelConfig::from_toml_str("gamma = 0.0").unwrap_err();
+    assert!(format!("{e:#}").contains("force constant must be a positive finite number"));
+    assert!(ModelConfig::from_toml_str("bonded_gammas = [10.0, -1.0]").is_err());
+    assert!(ModelConfig::from_toml_str("spring = { inverse_power = -1.0 }").is_err());
+    assert!(ModelConfig::from_toml_str("model = \"gnm\"\nbonded_gammas = [10.0]").is_err());
+
+    // bonded springs along chains of the selected structure
+    let config = ModelConfig::from_toml_str("cutoff = 5.0\nbonded_gammas = [10.0]")?;
+    assert!(config.anm().is_err());
+    let pdb = "\
+ATOM      1  CA  MET A   1      26.266  25.413   2.842  1.00 10.38           C
+ATOM      2  CA  GLN A   2      26.850  29.021   3.898  1.00  9.07           C
+ATOM      3  CA  ILE B   1      26.235  30.058   3.000  1.00  8.29           C
+";
+    let structure = Structure::from_pdb_str(pdb)?.select(&config.selection());
+    let anm = config.anm_for(&structure)?;
+    let bonded = anm.bonded.as_ref().unwrap();
+    assert_eq!(bonded.gammas, vec![10.0]);
+    assert_eq!(anm.force_constant(0, 1, 3.8), 10.0);
+    assert_eq!(anm.force_constant(1, 2, 3.8), 1.0);
+
+    Ok(())
+}
+// c3d95a17 ends here
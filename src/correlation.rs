@@ -0,0 +1,119 @@
+// [[file:../enm.note::4d7a1c3e][4d7a1c3e]]
+use nalgebra::DMatrix;
+use vecfx::*;
+
+use crate::{AnisotropicNetworkModel, GaussianNetworkModel, NormalModes};
+
+/// Compute normalized N*N cross-correlation matrix from covariance
+/// matrix `covariance` of `natoms` atoms, which could be either 3N*3N
+/// from ANM or N*N from GNM.
+pub fn cross_correlation_from_covariance(covariance: &DMatrix<f64>, natoms: usize) -> DMatrix<f64> {
+    let dim = covariance.nrows();
+    assert_eq!(dim, covariance.ncols(), "covariance matrix is not square");
+    assert!(dim == natoms || dim == 3 * natoms, "invalid covariance matrix size");
+    let dof = dim / natoms;
+
+    // inner product of displacement vectors: trace of 3x3 blocks
+    let mut c = DMatrix::<f64>::zeros(natoms, natoms);
+    for i in 0..natoms {
+        for j in 0..natoms {
+            c[(i, j)] = (0..dof).map(|k| covariance[(dof * i + k, dof * j + k)]).sum();
+        }
+    }
+
+    // normalize by the fluctuations of each atom
+    let diag = c.diagonal();
+    DMatrix::from_fn(natoms, natoms, |i, j| c[(i, j)] / (diag[i] * diag[j]).sqrt())
+}
+
+impl NormalModes {
+    /// Covariance matrix of atom displacements (3N*3N for ANM, N*N for
+    /// GNM) in units of kT, summed over the first `nmodes` modes (all
+    /// modes if None). With all modes this is the Moore–Penrose
+    /// pseudo-inverse of the Hessian (Kirchhoff) matrix.
+    pub fn covariance(&self, nmodes: impl Into<Option<usize>>) -> DMatrix<f64> {
+        let nmodes = nmodes.into().unwrap_or(self.nmodes()).min(self.nmodes());
+        let dim = self.eigenvectors().nrows();
+        let mut w = DMatrix::zeros(dim, nmodes);
+        for k in 0..nmodes {
+            let (value, vector) = self.mode(k);
+            let scale = 1.0 / value.sqrt();
+            if self.dof_per_atom() == 3 {
+                let d = self.displacements(k);
+                for (i, x) in d.iter().flatten().enumerate() {
+                    w[(i, k)] = x * scale;
+                }
+            } else {
+                for (i, x) in vector.iter().enumerate() {
+                    w[(i, k)] = x * scale;
+                }
+            }
+        }
+        &w * w.transpose()
+    }
+
+    /// Dynamical cross-correlation matrix (N*N) between atom
+    /// displacements, computed from the first `nmodes` modes (all
+    /// modes if None, equivalent to the pseudo-inverse of the Hessian
+    /// matrix).
+    pub fn cross_correlation(&self, nmodes: impl Into<Option<usize>>) -> DMatrix<f64> {
+        cross_correlation_from_covariance(&self.covariance(nmodes), self.natoms())
+    }
+}
+
+impl AnisotropicNetworkModel {
+    /// Covariance matrix (3N*3N) of atom displacements for Cartesian
+    /// `coords` at thermal energy `kt`, that is, kT times the
+    /// pseudo-inverse of the Hessian matrix with zero modes excluded.
+    /// Only the lowest `nmodes` modes are included if specified.
+    pub fn covariance<'a>(&self, coords: &[[f64; 3]], masses: impl Into<Option<&'a [f64]>>, kt: f64, nmodes: impl Into<Option<usize>>) -> DMatrix<f64> {
+        self.normal_modes(coords, masses).covariance(nmodes) * kt
+    }
+}
+
+impl GaussianNetworkModel {
+    /// Covariance matrix (N*N) of atom fluctuations for Cartesian
+    /// `coords` at thermal energy `kt`, that is, kT times the
+    /// pseudo-inverse of the Kirchhoff matrix with zero modes excluded.
+    /// Only the lowest `nmodes` modes are included if specified.
+    pub fn covariance(&self, coords: &[[f64; 3]], kt: f64, nmodes: impl Into<Option<usize>>) -> DMatrix<f64> {
+        self.normal_modes(coords).covariance(nmodes) * kt
+    }
+}
+
+#[test]
+fn test_cross_correlation() {
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    let anm = AnisotropicNetworkModel::default();
+    let modes = anm.normal_modes(&coords, None);
+    let c = modes.cross_correlation(None);
+    assert_eq!(c.shape(), (8, 8));
+    for i in 0..8 {
+        assert_relative_eq!(c[(i, i)], 1.0, epsilon = 1E-10);
+    }
+    assert_relative_eq!(c.clone(), c.transpose(), epsilon = 1E-10);
+    assert!(c.iter().all(|x| x.abs() <= 1.0 + 1E-10));
+
+    // all modes are equivalent to Hessian pseudo-inverse
+    let hessian = anm.build_hessian_matrix(&coords, None);
+    let pinv = hessian.pseudo_inverse(1E-6).unwrap();
+    let c_ref = cross_correlation_from_covariance(&pinv, 8);
+    assert_relative_eq!(c, c_ref, epsilon = 1E-6);
+    let cov = anm.covariance(&coords, None, 0.6, None);
+    assert_relative_eq!(cov, pinv * 0.6, epsilon = 1E-6);
+    let cov3 = anm.covariance(&coords, None, 0.6, 3);
+    assert_eq!(cov3.shape(), (24, 24));
+    assert!(cov3.trace() < cov.trace());
+
+    // a single mode gives perfectly (anti-)correlated GNM motions
+    let gnm = GaussianNetworkModel {
+        cutoff: 2.5,
+        ..Default::default()
+    };
+    let c = gnm.normal_modes(&coords).cross_correlation(1);
+    assert!(c.iter().all(|x| (x.abs() - 1.0).abs() < 1E-8));
+}
+// 4d7a1c3e ends here
@@ -0,0 +1,105 @@
+// [[file:../enm.note::5b1d7e0a][5b1d7e0a]]
+use gut::prelude::*;
+
+use crate::enm::atom_masses;
+use crate::modes::NormalModes;
+use crate::overlap::overlap;
+use crate::superpose::superpose;
+use crate::AnisotropicNetworkModel;
+
+/// Deformation vector (3N) from `start` to `target` conformation with
+/// identical atom ordering, after superimposing `target` onto `start`
+/// weighted by optional atom `masses`.
+pub fn deformation_vector<'a>(start: &[[f64; 3]], target: &[[f64; 3]], masses: impl Into<Option<&'a [f64]>>) -> Vec<f64> {
+    let masses = masses.into();
+    assert_eq!(start.len(), target.len(), "conformations differ in number of atoms");
+    if let Some(masses) = masses {
+        assert_eq!(masses.len(), start.len(), "invalid number of masses");
+    }
+    let aligned = superpose(target, start, masses).apply(target);
+    aligned.iter().zip(start).flat_map(|(a, b)| [a[0] - b[0], a[1] - b[1], a[2] - b[2]]).collect()
+}
+
+/// Overlaps of a conformational change with normal modes.
+#[derive(Debug, Clone)]
+pub struct ConformationalOverlap {
+    /// Overlap of the deformation vector with each mode
+    pub overlaps: Vec<f64>,
+    /// Cumulative overlap with the first k+1 modes for each mode k
+    pub cumulative: Vec<f64>,
+}
+
+impl NormalModes {
+    /// Overlaps of Cartesian `deformation` vector (3N) with the ANM
+    /// modes. For mass weighted modes the deformation is scaled by
+    /// sqrt(m) for each atom before comparison.
+    pub fn deformation_overlap(&self, deformation: &[f64]) -> ConformationalOverlap {
+        assert_eq!(self.dof_per_atom(), 3, "deformation overlap requires ANM modes");
+        assert_eq!(deformation.len(), 3 * self.natoms(), "invalid size of deformation vector");
+        let deformation = match self.masses() {
+            Some(masses) => deformation.iter().enumerate().map(|(k, d)| d * masses[k / 3].sqrt()).collect_vec(),
+            None => deformation.to_vec(),
+        };
+        let overlaps = self.iter().map(|(_, v)| overlap(&deformation, v)).collect_vec();
+        let cumulative = overlaps
+            .iter()
+            .scan(0.0, |acc, x| {
+                *acc += x * x;
+                Some(acc.sqrt())
+            })
+            .collect();
+        ConformationalOverlap { overlaps, cumulative }
+    }
+}
+
+impl AnisotropicNetworkModel {
+    /// Overlaps of the conformational change from `start` to `target`
+    /// with normal modes of the `start` structure.
+    ///
+    /// # References
+    ///
+    /// - Tama, F.; Sanejouand, Y.-H. Protein Engineering 2001, 14 (1), 1–6. <https://doi.org/10.1093/protein/14.1.1>
+    pub fn conformational_overlap<'a>(
+        &self,
+        start: &[[f64; 3]],
+        target: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+    ) -> ConformationalOverlap {
+        let masses = masses.into();
+        let modes = self.normal_modes(start, masses);
+        let weights = self.mass_weighted.then(|| atom_masses(start.len(), masses));
+        let deformation = deformation_vector(start, target, weights.as_deref());
+        modes.deformation_overlap(&deformation)
+    }
+}
+
+#[test]
+fn test_conformational_overlap() {
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    let anm = AnisotropicNetworkModel::default();
+    let modes = anm.normal_modes(&coords, None);
+
+    // displace along mode 1, then rotate and translate the target
+    let d = modes.displacements(1);
+    let (c, s) = (0.3f64.cos(), 0.3f64.sin());
+    let target = coords
+        .iter()
+        .zip(&d)
+        .map(|(p, d)| {
+            let (x, y, z) = (p[0] + 0.1 * d[0], p[1] + 0.1 * d[1], p[2] + 0.1 * d[2]);
+            [c * x - s * y + 1.0, s * x + c * y - 2.0, z + 0.5]
+        })
+        .collect_vec();
+
+    let deformation = deformation_vector(&coords, &target, None);
+    assert_eq!(deformation.len(), 24);
+    let result = anm.conformational_overlap(&coords, &target, None);
+    assert_eq!(result.overlaps.len(), modes.nmodes());
+    assert!(result.overlaps[1] > 0.99);
+    assert!(result.cumulative.windows(2).all(|w| w[0] <= w[1] + 1E-12));
+    assert_relative_eq!(result.cumulative.last().copied().unwrap(), 1.0, epsilon = 1E-3);
+}
+// 5b1d7e0a ends here
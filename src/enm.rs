@@ -1,19 +1,30 @@
 // [[file:../enm.note::d5052804][d5052804]]
 use gut::prelude::*;
 use nalgebra::DMatrix;
+use serde::{Deserialize, Serialize};
 use vecfx::*;
 
+use crate::neighbor::{find_contacts, Contact};
+use crate::spring::{BondedSprings, SpringConstant};
+
 /// Anisotropic Network Model (ANM) analysis
 ///
 /// # References
 ///
 /// - Atilgan, A. R. et al. Biophysical Journal 2001, 80 (1), 505–515. <https://doi.org/10.1016/S0006-3495(01)76033-X>
 /// - <https://en.wikipedia.org/wiki/Anisotropic_Network_Model>
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnisotropicNetworkModel {
     pub cutoff: f64,
     pub gamma: f64,
+    /// Distance dependence of spring force constants
+    pub spring: SpringConstant,
+    /// Stronger springs for sequence-bonded neighbors
+    pub bonded: Option<BondedSprings>,
     pub mass_weighted: bool,
+    /// Modes with absolute eigenvalue below this fraction of the largest
+    /// one are treated as zero modes of rigid-body motions.
+    pub zero_tolerance: f64,
 }
 
 impl Default for AnisotropicNetworkModel {
@@ -21,16 +32,37 @@ impl Default for AnisotropicNetworkModel {
         Self {
             cutoff: 15.0,
             gamma: 1.0,
+            spring: SpringConstant::Uniform,
+            bonded: None,
             mass_weighted: false,
+            zero_tolerance: 1E-6,
         }
     }
 }
 
-/// Calculates the normal modes by diagonalizing the Hessian matrix
-/// `hessian`. Returns 3N-6 eigen values sorted in ascending order and
-/// their associated eigen vectors with 6 translational and rotational
-/// modes removed.
-fn calculate_normal_modes(hessian: DMatrix<f64>) -> Vec<(f64, Vec<f64>)> {
+/// Convert eigenvalue of mass weighted Hessian matrix to frequency in
+/// cm-1. Negative eigenvalues of unstable modes are converted by their
+/// magnitude.
+pub(crate) fn eigenvalue_to_frequency(value: f64) -> f64 {
+    value.abs().sqrt() * 1302.79
+}
+
+/// Returns `masses` of N atoms, treating all atoms as Carbon if not
+/// provided.
+pub(crate) fn atom_masses(n: usize, masses: Option<&[f64]>) -> Vec<f64> {
+    match masses {
+        Some(masses) => masses.to_vec(),
+        None => vec![12.011; n],
+    }
+}
+
+/// Calculates the normal modes by diagonalizing the symmetric matrix
+/// `hessian`. Returns eigen values sorted in ascending order and their
+/// associated eigen vectors with zero modes removed, that is, the
+/// modes with absolute eigenvalue not larger than `tolerance` times the
+/// largest absolute eigenvalue, independent of the scale of force
+/// constants.
+pub(crate) fn calculate_normal_modes(hessian: DMatrix<f64>, tolerance: f64) -> Vec<(f64, Vec<f64>)> {
     let eigen = hessian.symmetric_eigen();
     let vectors = eigen.eigenvectors;
     let evalues = eigen.eigenvalues;
@@ -47,56 +79,75 @@ fn calculate_normal_modes(hessian: DMatrix<f64>) -> Vec<(f64, Vec<f64>)> {
     let mut evalues_ = vec![];
     let mut vectors_ = vec![];
     for &i in indices.iter() {
-        // FIXME: eigen value to frequency
-        // evalues_.push(evalues[i].sqrt() * 1302.79);
         evalues_.push(evalues[i]);
         vectors_.push(vectors.column(i).as_slice().to_owned());
     }
 
-    // skip the first 6 modes with zero eigenvalues for translation or rotation
-    evalues_.into_iter().zip(vectors_).skip(6).collect_vec()
+    // skip the modes with zero eigenvalues
+    let threshold = tolerance * evalues.amax();
+    evalues_.into_iter().zip(vectors_).filter(|x| x.0.abs() > threshold).collect_vec()
 }
 
 impl AnisotropicNetworkModel {
+    /// Returns all springs in the network for Cartesian `coords`, that
+    /// is, the atom pairs within `cutoff`, or all atom pairs if `spring`
+    /// does not use cutoff. Bonded neighbors are always included.
+    pub fn contacts(&self, coords: &[[f64; 3]]) -> Vec<Contact> {
+        let mut contacts = if self.spring.uses_cutoff() {
+            find_contacts(coords, self.cutoff)
+        } else {
+            find_contacts(coords, f64::INFINITY)
+        };
+        if let Some(bonded) = &self.bonded {
+            contacts.extend(bonded.contacts(coords));
+            contacts.sort_by_key(|c| (c.i, c.j));
+            contacts.dedup_by_key(|c| (c.i, c.j));
+        }
+        contacts
+    }
+
+    /// Force constant of the spring between atom `i` and atom `j`
+    /// separated by `distance`.
+    pub fn force_constant(&self, i: usize, j: usize, distance: f64) -> f64 {
+        self.bonded
+            .as_ref()
+            .and_then(|bonded| bonded.force_constant(i, j))
+            .unwrap_or_else(|| self.spring.force_constant(self.gamma, i, j, distance))
+    }
+
     /// Build Hessian matrix (3N*3N) for Cartesian `coords` of N atoms.
+    /// Panics if the number of `masses` is wrong; see
+    /// `try_build_hessian_matrix` for validated input.
     pub fn build_hessian_matrix<'a>(&self, coords: &[[f64; 3]], masses: impl Into<Option<&'a [f64]>>) -> DMatrix<f64> {
         let n = coords.len();
         let data = vec![0.0; 3 * n * 3 * n];
         let masses = masses.into();
-        if masses.is_some() {
-            assert_eq!(masses.unwrap().len(), n, "invalid number of masses");
+        if let Some(masses) = masses {
+            assert_eq!(masses.len(), n, "invalid number of masses");
         }
 
-        let gamma = self.gamma;
-        let cutoff2 = self.cutoff.powi(2);
-
         let mut hessian = DMatrix::from_vec(3 * n, 3 * n, data);
-        for i in 0..n {
-            for j in 0..i {
-                assert_ne!(i, j);
-                let ri: Vector3f = coords[i].into();
-                let rj: Vector3f = coords[j].into();
-                let rij = rj - ri;
-                let dist2 = (rj - ri).norm_squared();
-                if dist2 < cutoff2 {
-                    let super_element = -gamma / dist2 * rij * rij.transpose();
-                    let mut sub = hessian.fixed_slice_mut::<3, 3>(i * 3, j * 3);
-                    sub.copy_from(&super_element);
-                    let mut sub = hessian.fixed_slice_mut::<3, 3>(j * 3, i * 3);
-                    sub.copy_from(&super_element);
-                    let mut sub = hessian.fixed_slice_mut::<3, 3>(i * 3, i * 3);
-                    sub -= super_element;
-                    let mut sub = hessian.fixed_slice_mut::<3, 3>(j * 3, j * 3);
-                    sub -= super_element;
-                }
-                // mass weighted Hessian matrix for each atom
-                if self.mass_weighted {
-                    // treat as Carbon atom
-                    let mi = masses.map(|x| x[i]).unwrap_or(12.011);
-                    let mj = masses.map(|x| x[j]).unwrap_or(12.011);
-                    let mij_sqrt = mi.sqrt() * mj.sqrt();
-                    hessian[(i, j)] /= mij_sqrt;
-                    hessian[(j, i)] /= mij_sqrt;
+        for Contact { i, j, distance } in self.contacts(coords) {
+            let ri: Vector3f = coords[i].into();
+            let rj: Vector3f = coords[j].into();
+            let rij = rj - ri;
+            let gamma = self.force_constant(i, j, distance);
+            let super_element = -gamma / distance.powi(2) * rij * rij.transpose();
+            let mut sub = hessian.fixed_slice_mut::<3, 3>(i * 3, j * 3);
+            sub.copy_from(&super_element);
+            let mut sub = hessian.fixed_slice_mut::<3, 3>(j * 3, i * 3);
+            sub.copy_from(&super_element);
+            let mut sub = hessian.fixed_slice_mut::<3, 3>(i * 3, i * 3);
+            sub -= super_element;
+            let mut sub = hessian.fixed_slice_mut::<3, 3>(j * 3, j * 3);
+            sub -= super_element;
+        }
+        // mass weighted Hessian matrix: scale each 3x3 block (i, j) by 1/sqrt(mi*mj)
+        if self.mass_weighted {
+            let masses = atom_masses(n, masses);
+            for j in 0..3 * n {
+                for i in 0..3 * n {
+                    hessian[(i, j)] /= (masses[i / 3] * masses[j / 3]).sqrt();
                 }
             }
         }
@@ -104,41 +155,51 @@ impl AnisotropicNetworkModel {
     }
 
     /// Calculates the normal modes by diagonalizing the Hessian
-    /// matrix `hessian`. Returns 3N-6 eigen values sorted in
-    /// ascending order and their associated eigen vectors with 6
-    /// translational and rotational modes removed.
+    /// matrix `hessian`. Returns eigen values sorted in ascending
+    /// order and their associated eigen vectors with translational and
+    /// rotational modes removed. The zero modes are detected by
+    /// `zero_tolerance` on eigenvalues relative to the largest one, so
+    /// that disconnected networks with more than 6 zero modes are
+    /// handled properly.
     pub fn calculate_normal_modes(&self, hessian: DMatrix<f64>) -> Vec<(f64, Vec<f64>)> {
-        let eigen = hessian.symmetric_eigen();
-        let vectors = eigen.eigenvectors;
-        let evalues = eigen.eigenvalues;
-
-        // sort the eigenvalues in ascending order
-        let indices: Vec<_> = evalues
-            .iter()
-            .enumerate()
-            .sorted_by_key(|x| OrderedFloat(*x.1))
-            .map(|x| x.0)
-            .collect();
-
-        // sort the corresponding eigenvectors in ascending order
-        let mut evalues_ = vec![];
-        let mut vectors_ = vec![];
-        for &i in indices.iter() {
-            // eigen value to frequency in cm-1
-            if self.mass_weighted {
-                // FIXME: avoid NaN for very small eigenvalue, which could be negative
-                evalues_.push(evalues[i].abs().sqrt() * 1302.79);
-            } else {
-                evalues_.push(evalues[i]);
+        let mut modes = calculate_normal_modes(hessian, self.zero_tolerance);
+        if self.mass_weighted {
+            for (v, _) in modes.iter_mut() {
+                *v = eigenvalue_to_frequency(*v);
             }
-            vectors_.push(vectors.column(i).as_slice().to_owned());
         }
+        modes
+    }
 
-        // skip the first 6 modes with zero eigenvalues for translation or rotation
-        evalues_.into_iter().zip(vectors_).skip(6).collect_vec()
+    /// Transform eigenvector `mode` of the mass weighted Hessian matrix
+    /// back to Cartesian displacements by scaling the components of
+    /// each atom by 1/sqrt(m). The result is not normalized. `mode` is
+    /// returned as is if `mass_weighted` is false.
+    pub fn to_cartesian_displacements<'a>(&self, mode: &[f64], masses: impl Into<Option<&'a [f64]>>) -> Vec<f64> {
+        if !self.mass_weighted {
+            return mode.to_vec();
+        }
+        let n = mode.len() / 3;
+        let masses = atom_masses(n, masses.into());
+        assert_eq!(masses.len(), n, "invalid number of masses");
+        mode.iter().enumerate().map(|(i, x)| x / masses[i / 3].sqrt()).collect()
     }
 }
 
+/// Cartesian coordinates of 8 atoms for tests.
+#[cfg(test)]
+#[rustfmt::skip]
+pub(crate) fn test_coords() -> [[f64; 3]; 8] {
+    [[ -1.72300000,   1.18800000,   1.85600000],
+     [ -3.40400000,   0.60000000,   1.76800000],
+     [ -4.67400000,  -1.11300000,   0.60100000],
+     [ -2.96700000,  -0.68200000,   0.54500000],
+     [ -3.09400000,   2.29500000,   1.39200000],
+     [ -2.51000000,   1.07900000,   0.26100000],
+     [ -4.25300000,   0.54000000,   0.15700000],
+     [ -3.85700000,  -0.76600000,  -0.99200000]]
+}
+
 #[test]
 fn test_enm() {
     use approx::*;
@@ -166,4 +227,40 @@ fn test_enm() {
     assert_relative_eq!(vec[0], 0.22011, epsilon = 1E-4);
     assert_relative_eq!(vec[2], -0.36812, epsilon = 1E-4);
 }
+
+#[test]
+fn test_enm_mass_weighted() {
+    use approx::*;
+
+    let coords = test_coords();
+    let masses = [12.011, 14.007, 15.999, 12.011, 1.008, 32.06, 12.011, 14.007];
+
+    let anm = AnisotropicNetworkModel::default();
+    let hessian = anm.build_hessian_matrix(&coords, None);
+    let anm_mw = AnisotropicNetworkModel {
+        mass_weighted: true,
+        ..Default::default()
+    };
+    let hessian_mw = anm_mw.build_hessian_matrix(&coords, &masses[..]);
+    let sparse_mw = anm_mw.build_sparse_hessian_matrix(&coords, &masses[..]);
+    assert_relative_eq!(sparse_mw.to_dense(), hessian_mw, epsilon = 1E-10);
+
+    // M^{-1/2} H M^{-1/2}
+    let m = DMatrix::from_diagonal(&nalgebra::DVector::from_iterator(
+        3 * coords.len(),
+        masses.iter().flat_map(|&m| [1.0 / m.sqrt(); 3]),
+    ));
+    assert_relative_eq!(&m * &hessian * &m, hessian_mw, epsilon = 1E-10);
+
+    // Cartesian displacements satisfy H x = w M x
+    let eigen = hessian_mw.symmetric_eigen();
+    let k = eigen.eigenvalues.imax();
+    let x = anm_mw.to_cartesian_displacements(eigen.eigenvectors.column(k).as_slice(), &masses[..]);
+    let x = nalgebra::DVector::from_vec(x);
+    let mx = x.map_with_location(|i, _, v| v * masses[i / 3]);
+    assert_relative_eq!(hessian * &x, mx * eigen.eigenvalues[k], epsilon = 1E-8);
+
+    // unstable modes with negative eigenvalues have finite frequencies
+    assert_relative_eq!(eigenvalue_to_frequency(-4.0), 2.0 * 1302.79);
+}
 // d5052804 ends here
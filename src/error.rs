@@ -0,0 +1,522 @@
+// [[file:../enm.note::e1a6f4d8][e1a6f4d8]]
+use nalgebra::DMatrix;
+use vecfx::*;
+
+use crate::deformation::{deformation_vector, ConformationalOverlap};
+use crate::enm::atom_masses;
+use crate::lobpcg::LobpcgOptions;
+use crate::membrane::Membrane;
+use crate::modes::NormalModes;
+use crate::neighbor::find_contacts;
+use crate::sparse::SparseHessian;
+use crate::spring::SpringConstant;
+use crate::{AnisotropicNetworkModel, GaussianNetworkModel};
+
+/// Atoms closer than this are treated as coincident.
+const COINCIDENT_DISTANCE: f64 = 1E-6;
+
+/// Errors for invalid input of elastic network models.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnmError {
+    /// No atoms in input
+    EmptyInput,
+    /// The number of masses differs from the number of atoms
+    MassLengthMismatch { natoms: usize, nmasses: usize },
+    /// Mass of atom `atom` is not a positive finite number
+    InvalidMass { atom: usize, mass: f64 },
+    /// Coordinates of atom `atom` contain NaN or infinity
+    NonFiniteCoordinates { atom: usize },
+    /// Atom `i` and atom `j` are at the same position
+    CoincidentAtoms { i: usize, j: usize },
+    /// Too few atoms for any internal mode besides rigid-body modes
+    TooFewAtoms { natoms: usize, required: usize },
+    /// No internal modes besides zero modes, e.g. for a network of
+    /// isolated atoms
+    NoInternalModes,
+    /// Cutoff distance is not a positive finite number
+    InvalidCutoff { cutoff: f64 },
+    /// Force constant of springs is not a positive finite number
+    InvalidGamma { gamma: f64 },
+    /// Tolerance for zero modes is not a non-negative finite number
+    InvalidTolerance { tolerance: f64 },
+    /// Parameter of the spring function is out of its valid range
+    InvalidSpring { parameter: f64 },
+    /// The number of atoms in topology of bonded springs differs from
+    /// the number of atoms
+    TopologyMismatch { natoms: usize, ntopology: usize },
+    /// The iterative eigensolver did not converge in `iterations`
+    NotConverged { iterations: usize },
+    /// Parameter of the membrane slab is out of its valid range
+    InvalidMembrane { parameter: &'static str, value: f64 },
+    /// The number of block indices differs from the number of atoms
+    BlockLengthMismatch { natoms: usize, nblocks: usize },
+    /// Atom index `atom` is out of range
+    InvalidAtomIndex { atom: usize },
+    /// Atom `atom` is given more than once
+    DuplicateAtom { atom: usize },
+    /// Target conformation differs in the number of atoms
+    ConformationMismatch { natoms: usize, ntarget: usize },
+    /// Thermal energy is not a positive finite number
+    InvalidTemperature { kt: f64 },
+}
+
+impl std::fmt::Display for EnmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyInput => write!(f, "no atoms in input"),
+            Self::MassLengthMismatch { natoms, nmasses } => {
+                write!(f, "invalid number of masses: {nmasses} masses for {natoms} atoms")
+            }
+            Self::InvalidMass { atom, mass } => write!(f, "invalid mass of atom {atom}: {mass}"),
+            Self::NonFiniteCoordinates { atom } => write!(f, "non-finite coordinates of atom {atom}"),
+            Self::CoincidentAtoms { i, j } => write!(f, "atom {i} and atom {j} are at the same position"),
+            Self::TooFewAtoms { natoms, required } => {
+                write!(f, "too few atoms in the network: {natoms} atoms, at least {required} required")
+            }
+            Self::NoInternalModes => write!(f, "no internal modes in the network"),
+            Self::InvalidCutoff { cutoff } => write!(f, "cutoff must be a positive finite number, got {cutoff}"),
+            Self::InvalidGamma { gamma } => write!(f, "force constant must be a positive finite number, got {gamma}"),
+            Self::InvalidTolerance { tolerance } => write!(f, "zero tolerance must be a non-negative finite number, got {tolerance}"),
+            Self::InvalidSpring { parameter } => write!(f, "invalid parameter of spring function: {parameter}"),
+            Self::TopologyMismatch { natoms, ntopology } => {
+                write!(f, "invalid topology of bonded springs: {ntopology} atoms in topology for {natoms} atoms")
+            }
+            Self::NotConverged { iterations } => write!(f, "eigensolver not converged in {iterations} iterations"),
+            Self::InvalidMembrane { parameter, value } => write!(f, "invalid {parameter} of membrane: {value}"),
+            Self::BlockLengthMismatch { natoms, nblocks } => {
+                write!(f, "invalid number of block indices: {nblocks} indices for {natoms} atoms")
+            }
+            Self::InvalidAtomIndex { atom } => write!(f, "invalid atom index: {atom}"),
+            Self::DuplicateAtom { atom } => write!(f, "atom {atom} is given more than once"),
+            Self::ConformationMismatch { natoms, ntarget } => {
+                write!(f, "conformations differ in number of atoms: {natoms} and {ntarget}")
+            }
+            Self::InvalidTemperature { kt } => write!(f, "thermal energy must be a positive finite number, got {kt}"),
+        }
+    }
+}
+
+impl std::error::Error for EnmError {}
+
+/// Check Cartesian `coords` and optional atom `masses` for building a
+/// network with at least `min_atoms` atoms.
+pub fn validate_input(coords: &[[f64; 3]], masses: Option<&[f64]>, min_atoms: usize) -> Result<(), EnmError> {
+    let natoms = coords.len();
+    if natoms == 0 {
+        return Err(EnmError::EmptyInput);
+    }
+    if let Some(masses) = masses {
+        if masses.len() != natoms {
+            return Err(EnmError::MassLengthMismatch {
+                natoms,
+                nmasses: masses.len(),
+            });
+        }
+        if let Some((atom, &mass)) = masses.iter().enumerate().find(|(_, m)| !(m.is_finite() && **m > 0.0)) {
+            return Err(EnmError::InvalidMass { atom, mass });
+        }
+    }
+    if let Some(atom) = coords.iter().position(|p| !p.iter().all(|x| x.is_finite())) {
+        return Err(EnmError::NonFiniteCoordinates { atom });
+    }
+    if natoms < min_atoms {
+        return Err(EnmError::TooFewAtoms {
+            natoms,
+            required: min_atoms,
+        });
+    }
+    if let Some(c) = find_contacts(coords, COINCIDENT_DISTANCE).first() {
+        return Err(EnmError::CoincidentAtoms { i: c.j, j: c.i });
+    }
+    Ok(())
+}
+
+/// Check force constant `gamma` of springs.
+pub(crate) fn validate_gamma(gamma: f64) -> Result<(), EnmError> {
+    if !(gamma.is_finite() && gamma > 0.0) {
+        return Err(EnmError::InvalidGamma { gamma });
+    }
+    Ok(())
+}
+
+/// Check `cutoff`, `gamma`, parameters of `spring` and `zero_tolerance`
+/// shared by ANM and GNM, also for model configuration.
+pub(crate) fn validate_springs(cutoff: f64, gamma: f64, spring: &SpringConstant, zero_tolerance: f64) -> Result<(), EnmError> {
+    if spring.uses_cutoff() && !(cutoff.is_finite() && cutoff > 0.0) {
+        return Err(EnmError::InvalidCutoff { cutoff });
+    }
+    validate_gamma(gamma)?;
+    if !(zero_tolerance.is_finite() && zero_tolerance >= 0.0) {
+        return Err(EnmError::InvalidTolerance {
+            tolerance: zero_tolerance,
+        });
+    }
+    match *spring {
+        SpringConstant::InversePower(p) if !(p.is_finite() && p >= 0.0) => Err(EnmError::InvalidSpring { parameter: p }),
+        SpringConstant::Exponential(r0) if !(r0.is_finite() && r0 > 0.0) => Err(EnmError::InvalidSpring { parameter: r0 }),
+        _ => Ok(()),
+    }
+}
+
+/// Check that there are modes left after removing zero modes.
+fn ensure_internal_modes(modes: NormalModes) -> Result<NormalModes, EnmError> {
+    if modes.nmodes() == 0 {
+        return Err(EnmError::NoInternalModes);
+    }
+    Ok(modes)
+}
+
+/// Check thermal energy `kt`.
+fn validate_temperature(kt: f64) -> Result<(), EnmError> {
+    if !(kt.is_finite() && kt > 0.0) {
+        return Err(EnmError::InvalidTemperature { kt });
+    }
+    Ok(())
+}
+
+impl AnisotropicNetworkModel {
+    /// Check model parameters for building a network of `natoms` atoms.
+    pub fn validate_parameters(&self, natoms: usize) -> Result<(), EnmError> {
+        validate_springs(self.cutoff, self.gamma, &self.spring, self.zero_tolerance)?;
+        if let Some(bonded) = &self.bonded {
+            let topology = &bonded.topology;
+            for ntopology in [topology.chains.len(), topology.residues.len()] {
+                if ntopology != natoms {
+                    return Err(EnmError::TopologyMismatch { natoms, ntopology });
+                }
+            }
+            for &gamma in &bonded.gammas {
+                validate_gamma(gamma)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The same as `build_hessian_matrix`, but returns an error for
+    /// invalid input instead of panicking or producing NaN.
+    pub fn try_build_hessian_matrix<'a>(
+        &self,
+        coords: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+    ) -> Result<DMatrix<f64>, EnmError> {
+        let masses = masses.into();
+        validate_input(coords, masses, 3)?;
+        self.validate_parameters(coords.len())?;
+        Ok(self.build_hessian_matrix(coords, masses))
+    }
+
+    /// The same as `build_sparse_hessian_matrix`, but returns an error
+    /// for invalid input.
+    pub fn try_build_sparse_hessian_matrix<'a>(
+        &self,
+        coords: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+    ) -> Result<SparseHessian, EnmError> {
+        let masses = masses.into();
+        validate_input(coords, masses, 3)?;
+        self.validate_parameters(coords.len())?;
+        Ok(self.build_sparse_hessian_matrix(coords, masses))
+    }
+
+    /// The same as `normal_modes`, but returns an error for invalid
+    /// input or if no internal modes are left.
+    pub fn try_normal_modes<'a>(&self, coords: &[[f64; 3]], masses: impl Into<Option<&'a [f64]>>) -> Result<NormalModes, EnmError> {
+        let masses = masses.into();
+        validate_input(coords, masses, 3)?;
+        self.validate_parameters(coords.len())?;
+        ensure_internal_modes(self.normal_modes(coords, masses))
+    }
+
+    /// The same as `lowest_normal_modes`, but returns an error for
+    /// invalid input.
+    pub fn try_lowest_normal_modes<'a>(
+        &self,
+        coords: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+        k: usize,
+        options: &LobpcgOptions,
+    ) -> Result<NormalModes, EnmError> {
+        let masses = masses.into();
+        validate_input(coords, masses, 3)?;
+        self.validate_parameters(coords.len())?;
+        ensure_internal_modes(self.lowest_normal_modes(coords, masses, k, options)?)
+    }
+
+    /// The same as `rtb_normal_modes`, but returns an error for invalid
+    /// input.
+    pub fn try_rtb_normal_modes<'a>(
+        &self,
+        coords: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+        blocks: &[usize],
+    ) -> Result<NormalModes, EnmError> {
+        let masses = masses.into();
+        validate_input(coords, masses, 3)?;
+        self.validate_parameters(coords.len())?;
+        if blocks.len() != coords.len() {
+            return Err(EnmError::BlockLengthMismatch {
+                natoms: coords.len(),
+                nblocks: blocks.len(),
+            });
+        }
+        ensure_internal_modes(self.rtb_normal_modes(coords, masses, blocks))
+    }
+
+    /// The same as `reduced_normal_modes`, but returns an error for
+    /// invalid input.
+    pub fn try_reduced_normal_modes<'a>(
+        &self,
+        coords: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+        system: &[usize],
+        environment: &[usize],
+    ) -> Result<NormalModes, EnmError> {
+        let masses = masses.into();
+        validate_input(coords, masses, 1)?;
+        self.validate_parameters(coords.len())?;
+        if system.is_empty() {
+            return Err(EnmError::EmptyInput);
+        }
+        let mut seen = vec![false; coords.len()];
+        for &atom in system.iter().chain(environment) {
+            if atom >= coords.len() {
+                return Err(EnmError::InvalidAtomIndex { atom });
+            }
+            if std::mem::replace(&mut seen[atom], true) {
+                return Err(EnmError::DuplicateAtom { atom });
+            }
+        }
+        ensure_internal_modes(self.reduced_normal_modes(coords, masses, system, environment))
+    }
+
+    /// The same as `membrane_normal_modes`, but returns an error for
+    /// invalid input.
+    pub fn try_membrane_normal_modes(&self, coords: &[[f64; 3]], membrane: &Membrane) -> Result<NormalModes, EnmError> {
+        validate_input(coords, None, 3)?;
+        self.validate_parameters(coords.len())?;
+        ensure_internal_modes(self.membrane_normal_modes(coords, membrane)?)
+    }
+
+    /// The same as `covariance`, but returns an error for invalid input.
+    pub fn try_covariance<'a>(
+        &self,
+        coords: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+        kt: f64,
+        nmodes: impl Into<Option<usize>>,
+    ) -> Result<DMatrix<f64>, EnmError> {
+        validate_temperature(kt)?;
+        Ok(self.try_normal_modes(coords, masses)?.covariance(nmodes) * kt)
+    }
+
+    /// The same as `conformational_overlap`, but returns an error for
+    /// invalid input.
+    pub fn try_conformational_overlap<'a>(
+        &self,
+        start: &[[f64; 3]],
+        target: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+    ) -> Result<ConformationalOverlap, EnmError> {
+        let masses = masses.into();
+        if target.len() != start.len() {
+            return Err(EnmError::ConformationMismatch {
+                natoms: start.len(),
+                ntarget: target.len(),
+            });
+        }
+        if let Some(atom) = target.iter().position(|p| !p.iter().all(|x| x.is_finite())) {
+            return Err(EnmError::NonFiniteCoordinates { atom });
+        }
+        let modes = self.try_normal_modes(start, masses)?;
+        let weights = self.mass_weighted.then(|| atom_masses(start.len(), masses));
+        let deformation = deformation_vector(start, target, weights.as_deref());
+        Ok(modes.deformation_overlap(&deformation))
+    }
+}
+
+impl GaussianNetworkModel {
+    /// Check model parameters.
+    pub fn validate_parameters(&self) -> Result<(), EnmError> {
+        validate_springs(self.cutoff, self.gamma, &self.spring, self.zero_tolerance)
+    }
+
+    /// The same as `build_kirchhoff_matrix`, but returns an error for
+    /// invalid input.
+    pub fn try_build_kirchhoff_matrix(&self, coords: &[[f64; 3]]) -> Result<DMatrix<f64>, EnmError> {
+        validate_input(coords, None, 2)?;
+        self.validate_parameters()?;
+        Ok(self.build_kirchhoff_matrix(coords))
+    }
+
+    /// The same as `normal_modes`, but returns an error for invalid
+    /// input or if no internal modes are left.
+    pub fn try_normal_modes(&self, coords: &[[f64; 3]]) -> Result<NormalModes, EnmError> {
+        validate_input(coords, None, 2)?;
+        self.validate_parameters()?;
+        ensure_internal_modes(self.normal_modes(coords))
+    }
+
+    /// The same as `covariance`, but returns an error for invalid input.
+    pub fn try_covariance(&self, coords: &[[f64; 3]], kt: f64, nmodes: impl Into<Option<usize>>) -> Result<DMatrix<f64>, EnmError> {
+        validate_temperature(kt)?;
+        Ok(self.try_normal_modes(coords)?.covariance(nmodes) * kt)
+    }
+}
+
+#[test]
+fn test_enm_error() {
+    let coords = crate::enm::test_coords();
+
+    let anm = AnisotropicNetworkModel::default();
+    assert!(anm.try_build_hessian_matrix(&coords, None).is_ok());
+    assert_eq!(anm.try_normal_modes(&[], None).unwrap_err(), EnmError::EmptyInput);
+    assert_eq!(
+        anm.try_build_hessian_matrix(&coords, &[12.0; 3][..]).unwrap_err(),
+        EnmError::MassLengthMismatch { natoms: 8, nmasses: 3 }
+    );
+    assert_eq!(
+        anm.try_build_hessian_matrix(&coords, &[12.0, 0.0, 12.0, 12.0, 12.0, 12.0, 12.0, 12.0][..]).unwrap_err(),
+        EnmError::InvalidMass { atom: 1, mass: 0.0 }
+    );
+    assert_eq!(
+        anm.try_normal_modes(&coords[..2], None).unwrap_err(),
+        EnmError::TooFewAtoms { natoms: 2, required: 3 }
+    );
+
+    let mut bad = coords;
+    bad[2][1] = f64::NAN;
+    assert_eq!(
+        anm.try_build_sparse_hessian_matrix(&bad, None).unwrap_err(),
+        EnmError::NonFiniteCoordinates { atom: 2 }
+    );
+    let mut bad = coords;
+    bad[3] = bad[1];
+    let e = anm.try_build_hessian_matrix(&bad, None).unwrap_err();
+    assert_eq!(e, EnmError::CoincidentAtoms { i: 1, j: 3 });
+    assert_eq!(e.to_string(), "atom 1 and atom 3 are at the same position");
+
+    // invalid model parameters
+    for cutoff in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        let anm = AnisotropicNetworkModel {
+            cutoff,
+            ..Default::default()
+        };
+        assert!(matches!(anm.try_normal_modes(&coords, None), Err(EnmError::InvalidCutoff { .. })));
+    }
+    // cutoff is not used by pfANM
+    let anm = AnisotropicNetworkModel {
+        cutoff: 0.0,
+        spring: SpringConstant::ParameterFree,
+        ..Default::default()
+    };
+    assert!(anm.try_build_hessian_matrix(&coords, None).is_ok());
+    for gamma in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        let anm = AnisotropicNetworkModel {
+            gamma,
+            ..Default::default()
+        };
+        assert!(matches!(anm.try_build_hessian_matrix(&coords, None), Err(EnmError::InvalidGamma { .. })));
+    }
+    let anm = AnisotropicNetworkModel {
+        spring: SpringConstant::Exponential(0.0),
+        ..Default::default()
+    };
+    assert_eq!(
+        anm.try_build_sparse_hessian_matrix(&coords, None).unwrap_err(),
+        EnmError::InvalidSpring { parameter: 0.0 }
+    );
+    let anm = AnisotropicNetworkModel {
+        spring: SpringConstant::InversePower(-1.0),
+        ..Default::default()
+    };
+    assert_eq!(anm.try_normal_modes(&coords, None).unwrap_err(), EnmError::InvalidSpring { parameter: -1.0 });
+    let anm = AnisotropicNetworkModel {
+        zero_tolerance: -1.0,
+        ..Default::default()
+    };
+    assert_eq!(
+        anm.try_normal_modes(&coords, None).unwrap_err(),
+        EnmError::InvalidTolerance { tolerance: -1.0 }
+    );
+
+    // isolated atoms: no springs, no internal modes
+    let anm = AnisotropicNetworkModel::default();
+    let far = [[0.0; 3], [100.0, 0.0, 0.0], [0.0, 100.0, 0.0]];
+    assert_eq!(anm.try_normal_modes(&far, None).unwrap_err(), EnmError::NoInternalModes);
+    assert_eq!(
+        GaussianNetworkModel::default().try_normal_modes(&far).unwrap_err(),
+        EnmError::NoInternalModes
+    );
+
+    // other entry points
+    assert!(anm.try_rtb_normal_modes(&coords, None, &[0, 0, 0, 0, 1, 1, 1, 1]).is_ok());
+    assert_eq!(
+        anm.try_rtb_normal_modes(&coords, None, &[0, 1]).unwrap_err(),
+        EnmError::BlockLengthMismatch { natoms: 8, nblocks: 2 }
+    );
+    assert!(anm.try_reduced_normal_modes(&coords, None, &[0, 1, 2, 3], &[4, 5, 6, 7]).is_ok());
+    assert_eq!(
+        anm.try_reduced_normal_modes(&coords, None, &[0, 1, 8], &[]).unwrap_err(),
+        EnmError::InvalidAtomIndex { atom: 8 }
+    );
+    assert_eq!(
+        anm.try_reduced_normal_modes(&coords, None, &[0, 1, 2], &[2, 3]).unwrap_err(),
+        EnmError::DuplicateAtom { atom: 2 }
+    );
+    assert_eq!(anm.try_reduced_normal_modes(&coords, None, &[], &[0]).unwrap_err(), EnmError::EmptyInput);
+    let membrane = Membrane {
+        normal: [0.0; 3],
+        ..Default::default()
+    };
+    assert!(matches!(
+        anm.try_membrane_normal_modes(&coords, &membrane),
+        Err(EnmError::InvalidMembrane { .. })
+    ));
+    assert!(anm.try_covariance(&coords, None, 1.0, None).is_ok());
+    assert!(matches!(anm.try_covariance(&coords, None, f64::NAN, None), Err(EnmError::InvalidTemperature { .. })));
+    assert!(matches!(
+        GaussianNetworkModel::default().try_covariance(&coords, 0.0, None),
+        Err(EnmError::InvalidTemperature { .. })
+    ));
+    let target: Vec<_> = coords.iter().map(|&[x, y, z]| [x + 0.1, y, z]).collect();
+    assert!(anm.try_conformational_overlap(&coords, &target, None).is_ok());
+    assert_eq!(
+        anm.try_conformational_overlap(&coords, &target[..3], None).unwrap_err(),
+        EnmError::ConformationMismatch { natoms: 8, ntarget: 3 }
+    );
+    let mut target = target;
+    target[5][2] = f64::INFINITY;
+    assert_eq!(
+        anm.try_conformational_overlap(&coords, &target, None).unwrap_err(),
+        EnmError::NonFiniteCoordinates { atom: 5 }
+    );
+
+    // topology of bonded springs for a different number of atoms
+    let topology = crate::spring::Topology {
+        chains: vec![0; 7],
+        residues: (1..=7).collect(),
+    };
+    let anm = AnisotropicNetworkModel {
+        bonded: Some(crate::spring::BondedSprings {
+            topology,
+            gammas: vec![10.0],
+        }),
+        ..Default::default()
+    };
+    assert_eq!(
+        anm.try_normal_modes(&coords, None).unwrap_err(),
+        EnmError::TopologyMismatch { natoms: 8, ntopology: 7 }
+    );
+
+    let gnm = GaussianNetworkModel {
+        cutoff: f64::NAN,
+        ..Default::default()
+    };
+    assert!(matches!(gnm.try_normal_modes(&coords), Err(EnmError::InvalidCutoff { .. })));
+
+    let gnm = GaussianNetworkModel::default();
+    assert!(gnm.try_normal_modes(&coords).is_ok());
+    assert!(gnm.try_build_kirchhoff_matrix(&bad).is_err());
+    assert!(gnm.try_normal_modes(&coords[..1]).is_err());
+}
+// e1a6f4d8 ends here
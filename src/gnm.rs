@@ -0,0 +1,96 @@
+// [[file:../enm.note::3f9c61a2][3f9c61a2]]
+use nalgebra::DMatrix;
+use serde::{Deserialize, Serialize};
+use vecfx::*;
+
+use crate::neighbor::{find_contacts, Contact};
+use crate::spring::SpringConstant;
+
+/// Gaussian Network Model (GNM) analysis
+///
+/// # References
+///
+/// - Bahar, I. et al. Folding and Design 1997, 2 (3), 173–181. <https://doi.org/10.1016/S1359-0278(97)00024-2>
+/// - <https://en.wikipedia.org/wiki/Gaussian_network_model>
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GaussianNetworkModel {
+    pub cutoff: f64,
+    pub gamma: f64,
+    /// Distance dependence of spring force constants
+    pub spring: SpringConstant,
+    /// Modes with absolute eigenvalue below this fraction of the largest
+    /// one are treated as zero modes.
+    pub zero_tolerance: f64,
+}
+
+impl Default for GaussianNetworkModel {
+    fn default() -> Self {
+        Self {
+            cutoff: 7.3,
+            gamma: 1.0,
+            spring: SpringConstant::Uniform,
+            zero_tolerance: 1E-6,
+        }
+    }
+}
+
+impl GaussianNetworkModel {
+    /// Returns all springs in the network for Cartesian `coords`, that
+    /// is, the atom pairs within `cutoff`, or all atom pairs if `spring`
+    /// does not use cutoff.
+    pub fn contacts(&self, coords: &[[f64; 3]]) -> Vec<Contact> {
+        if self.spring.uses_cutoff() {
+            find_contacts(coords, self.cutoff)
+        } else {
+            find_contacts(coords, f64::INFINITY)
+        }
+    }
+
+    /// Build Kirchhoff matrix (N*N) for Cartesian `coords` of N atoms.
+    pub fn build_kirchhoff_matrix(&self, coords: &[[f64; 3]]) -> DMatrix<f64> {
+        let n = coords.len();
+        let mut kirchhoff = DMatrix::zeros(n, n);
+        for Contact { i, j, distance } in self.contacts(coords) {
+            let gamma = self.spring.force_constant(self.gamma, i, j, distance);
+            kirchhoff[(i, j)] = -gamma;
+            kirchhoff[(j, i)] = -gamma;
+            kirchhoff[(i, i)] += gamma;
+            kirchhoff[(j, j)] += gamma;
+        }
+        kirchhoff
+    }
+
+    /// Calculates the normal modes by diagonalizing the Kirchhoff
+    /// matrix `kirchhoff`. Returns eigen values sorted in ascending
+    /// order and their associated eigen vectors with zero modes
+    /// removed, one for each connected component of the network.
+    pub fn calculate_normal_modes(&self, kirchhoff: DMatrix<f64>) -> Vec<(f64, Vec<f64>)> {
+        crate::enm::calculate_normal_modes(kirchhoff, self.zero_tolerance)
+    }
+}
+
+#[test]
+fn test_gnm() {
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    let gnm = GaussianNetworkModel {
+        cutoff: 2.5,
+        ..Default::default()
+    };
+    let kirchhoff = gnm.build_kirchhoff_matrix(&coords);
+    // each row of Kirchhoff matrix sums to zero
+    for i in 0..coords.len() {
+        assert_relative_eq!(kirchhoff.row(i).sum(), 0.0, epsilon = 1E-10);
+    }
+
+    let modes = gnm.calculate_normal_modes(kirchhoff);
+    assert_eq!(modes.len(), coords.len() - 1);
+    assert!(modes[0].0 > 1E-6);
+    // the sum of eigenvalues equals the trace of Kirchhoff matrix
+    let trace: f64 = modes.iter().map(|x| x.0).sum();
+    let kirchhoff = gnm.build_kirchhoff_matrix(&coords);
+    assert_relative_eq!(trace, kirchhoff.trace(), epsilon = 1E-8);
+}
+// 3f9c61a2 ends here
@@ -1,7 +1,46 @@
 // [[file:../enm.note::a8b9ab5d][a8b9ab5d]]
 // #![deny(warnings)]
 
+mod archive;
+mod bfactor;
+mod config;
+mod correlation;
+mod deformation;
 mod enm;
+mod error;
+mod gnm;
+mod lobpcg;
+mod membrane;
+mod modes;
+mod neighbor;
+mod nmd;
+mod overlap;
+mod pdb;
+mod reduced;
+mod rigid;
+mod rtb;
+mod sparse;
+mod spring;
+mod superpose;
+mod trajectory;
 
+pub use crate::archive::*;
+pub use crate::bfactor::*;
+pub use crate::config::*;
+pub use crate::correlation::*;
+pub use crate::deformation::*;
 pub use crate::enm::*;
+pub use crate::error::*;
+pub use crate::gnm::*;
+pub use crate::lobpcg::*;
+pub use crate::membrane::*;
+pub use crate::modes::*;
+pub use crate::neighbor::*;
+pub use crate::overlap::*;
+pub use crate::pdb::*;
+pub use crate::rigid::*;
+pub use crate::sparse::*;
+pub use crate::spring::*;
+pub use crate::superpose::*;
+pub use crate::trajectory::*;
 // a8b9ab5d ends here
@@ -0,0 +1,372 @@
+// [[file:../enm.note::e0a4c6d3][e0a4c6d3]]
+use gut::prelude::*;
+use nalgebra::{DMatrix, DVector};
+use vecfx::*;
+
+use crate::enm::eigenvalue_to_frequency;
+use crate::{AnisotropicNetworkModel, EnmError, GaussianNetworkModel, SparseHessian};
+
+/// A real symmetric matrix that can be multiplied with a block of
+/// vectors without being stored in dense form.
+pub trait SymmetricOperator {
+    /// The dimension of the matrix.
+    fn dim(&self) -> usize;
+
+    /// Returns the product of the matrix with the columns of `x`.
+    fn apply(&self, x: &DMatrix<f64>) -> DMatrix<f64>;
+
+    /// Diagonal elements of the matrix for Jacobi preconditioning, if
+    /// available.
+    fn diagonal(&self) -> Option<Vec<f64>> {
+        None
+    }
+}
+
+impl SymmetricOperator for DMatrix<f64> {
+    fn dim(&self) -> usize {
+        self.nrows()
+    }
+
+    fn apply(&self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        self * x
+    }
+
+    fn diagonal(&self) -> Option<Vec<f64>> {
+        Some(self.diagonal().iter().copied().collect())
+    }
+}
+
+impl SymmetricOperator for SparseHessian {
+    fn dim(&self) -> usize {
+        SparseHessian::dim(self)
+    }
+
+    fn apply(&self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let mut y = DMatrix::zeros(x.nrows(), x.ncols());
+        for (k, col) in x.column_iter().enumerate() {
+            let v = self.dot(col.as_slice());
+            y.column_mut(k).copy_from_slice(&v);
+        }
+        y
+    }
+
+    fn diagonal(&self) -> Option<Vec<f64>> {
+        let mut diagonal = vec![0.0; self.dim()];
+        for i in 0..self.natoms() {
+            if let Some(block) = self.block(i, i) {
+                for k in 0..3 {
+                    diagonal[3 * i + k] = block[(k, k)];
+                }
+            }
+        }
+        Some(diagonal)
+    }
+}
+
+/// Settings of the iterative eigensolver `lobpcg`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LobpcgOptions {
+    /// Convergence threshold on residual norms, relative to the largest
+    /// Ritz value
+    pub tol: f64,
+    /// The maximum number of iterations
+    pub max_iter: usize,
+}
+
+impl Default for LobpcgOptions {
+    fn default() -> Self {
+        Self {
+            tol: 1E-8,
+            max_iter: 10000,
+        }
+    }
+}
+
+/// Eigenpairs computed by `lobpcg`.
+#[derive(Debug, Clone)]
+pub struct LobpcgResult {
+    /// Eigenvalues in ascending order
+    pub values: Vec<f64>,
+    /// Eigenvectors as columns
+    pub vectors: DMatrix<f64>,
+    /// True if the residuals of all eigenpairs are below tolerance
+    pub converged: bool,
+    /// The number of iterations performed
+    pub iterations: usize,
+}
+
+/// Orthonormalize the columns of `w` against the orthonormal columns
+/// of `x` and against each other. Columns that are linearly dependent
+/// are dropped.
+pub(crate) fn orthonormalize(x: &DMatrix<f64>, w: DMatrix<f64>) -> DMatrix<f64> {
+    let mut columns: Vec<DVector<f64>> = vec![];
+    for col in w.column_iter() {
+        let mut v = col.clone_owned();
+        let norm0 = v.norm();
+        if norm0 == 0.0 {
+            continue;
+        }
+        // two passes of Gram-Schmidt for numerical stability
+        for _ in 0..2 {
+            v -= x * (x.transpose() * &v);
+            for u in columns.iter() {
+                v -= u * u.dot(&v);
+            }
+        }
+        let norm = v.norm();
+        if norm > 1E-8 * norm0 {
+            columns.push(v / norm);
+        }
+    }
+    if columns.is_empty() {
+        DMatrix::zeros(x.nrows(), 0)
+    } else {
+        DMatrix::from_columns(&columns)
+    }
+}
+
+/// Solve the small symmetric eigenvalue problem, returning eigenvalues
+/// in ascending order and the corresponding eigenvectors.
+fn sorted_symmetric_eigen(t: DMatrix<f64>) -> (Vec<f64>, DMatrix<f64>) {
+    let t = (&t + t.transpose()) * 0.5;
+    let eigen = t.symmetric_eigen();
+    let indices = (0..eigen.eigenvalues.len())
+        .sorted_by_key(|&i| OrderedFloat(eigen.eigenvalues[i]))
+        .collect_vec();
+    let values = indices.iter().map(|&i| eigen.eigenvalues[i]).collect();
+    let vectors = DMatrix::from_columns(&indices.iter().map(|&i| eigen.eigenvectors.column(i)).collect_vec());
+    (values, vectors)
+}
+
+/// Deterministic pseudo-random initial guess in [-0.5, 0.5).
+fn initial_guess(n: usize, m: usize) -> DMatrix<f64> {
+    let mut state: u64 = 0x9E3779B97F4A7C15;
+    DMatrix::from_fn(n, m, |_, _| {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 11) as f64 / (1u64 << 53) as f64 - 0.5
+    })
+}
+
+/// Computes the lowest `nev` eigenpairs of symmetric matrix `op` using
+/// the locally optimal block preconditioned conjugate gradient
+/// (LOBPCG) method, with Jacobi preconditioner if the diagonal of `op`
+/// is available. Unconverged Ritz pairs are returned with `converged`
+/// set to false after `max_iter` iterations.
+///
+/// # References
+///
+/// - Knyazev, A. V. SIAM J. Sci. Comput. 2001, 23 (2), 517–541. <https://doi.org/10.1137/S1064827500366124>
+pub fn lobpcg(op: &impl SymmetricOperator, nev: usize, tol: f64, max_iter: usize) -> LobpcgResult {
+    let n = op.dim();
+    let nev = nev.min(n);
+    // a few more vectors in the block to speed up convergence
+    let m = (nev + nev.max(4)).min(n);
+
+    // the subspace is too large for iterative solver
+    if 3 * m >= n {
+        let dense = op.apply(&DMatrix::identity(n, n));
+        let (values, vectors) = sorted_symmetric_eigen(dense);
+        return LobpcgResult {
+            values: values[..nev].to_vec(),
+            vectors: vectors.columns(0, nev).into_owned(),
+            converged: true,
+            iterations: 0,
+        };
+    }
+    // inverse of positive diagonal elements
+    let preconditioner = op.diagonal().map(|d| d.into_iter().map(|x| if x > 0.0 { 1.0 / x } else { 1.0 }).collect_vec());
+
+    // Rayleigh-Ritz on the initial subspace
+    let x = orthonormalize(&DMatrix::zeros(n, 0), initial_guess(n, m));
+    let ax = op.apply(&x);
+    let (mut theta, c) = sorted_symmetric_eigen(x.transpose() * &ax);
+    let mut x = x * &c;
+    let mut ax = ax * &c;
+    let mut p: Option<DMatrix<f64>> = None;
+    let mut norm_estimate = theta.iter().fold(0.0f64, |acc, v| acc.max(v.abs()));
+
+    let mut converged = false;
+    let mut iterations = 0;
+    while iterations < max_iter {
+        // residuals for current Ritz pairs
+        let mut r = &ax - &x * DMatrix::from_diagonal(&DVector::from_column_slice(&theta));
+        converged = (0..nev).all(|i| r.column(i).norm() <= tol * norm_estimate.max(1E-300));
+        if converged {
+            break;
+        }
+        if let Some(t) = &preconditioner {
+            for mut col in r.column_iter_mut() {
+                col.iter_mut().zip(t).for_each(|(x, t)| *x *= t);
+            }
+        }
+        iterations += 1;
+
+        // search directions: residuals and previous step directions
+        let w = match p.take() {
+            Some(p) => DMatrix::from_columns(&r.column_iter().chain(p.column_iter()).collect_vec()),
+            None => r,
+        };
+        let w = orthonormalize(&x, w);
+        if w.ncols() == 0 {
+            break;
+        }
+        let aw = op.apply(&w);
+
+        // Rayleigh-Ritz on the subspace [X, W]
+        let s = DMatrix::from_columns(&x.column_iter().chain(w.column_iter()).collect_vec());
+        let a_s = DMatrix::from_columns(&ax.column_iter().chain(aw.column_iter()).collect_vec());
+        let (values, y) = sorted_symmetric_eigen(s.transpose() * &a_s);
+        norm_estimate = values.iter().fold(norm_estimate, |acc, v| acc.max(v.abs()));
+        let y = y.columns(0, m).into_owned();
+        theta = values[..m].to_vec();
+        x = &s * &y;
+        ax = &a_s * &y;
+        p = Some(&w * y.rows(m, w.ncols()));
+    }
+    if !converged {
+        // check residuals of the last update
+        let r = &ax - &x * DMatrix::from_diagonal(&DVector::from_column_slice(&theta));
+        converged = (0..nev).all(|i| r.column(i).norm() <= tol * norm_estimate.max(1E-300));
+    }
+    if converged {
+        debug!("LOBPCG converged in {iterations} iterations.");
+    } else {
+        warn!("LOBPCG not converged in {iterations} iterations.");
+    }
+
+    LobpcgResult {
+        values: theta[..nev].to_vec(),
+        vectors: x.columns(0, nev).into_owned(),
+        converged,
+        iterations,
+    }
+}
+
+/// Collect the lowest `k` nonzero eigenpairs from `lobpcg` in the
+/// same layout as `calculate_normal_modes`. Zero modes are detected by
+/// `tolerance` relative to the largest diagonal element of `op`, which
+/// has the same scale as the largest eigenvalue, starting with a guess
+/// of `nzero` zero modes. Returns an error if the eigensolver does not
+/// converge.
+pub(crate) fn calculate_lowest_modes(
+    op: &impl SymmetricOperator,
+    k: usize,
+    nzero: usize,
+    tolerance: f64,
+    options: &LobpcgOptions,
+) -> Result<Vec<(f64, Vec<f64>)>, EnmError> {
+    let scale = op.diagonal().map(|d| d.iter().fold(0.0f64, |acc, x| acc.max(x.abs())));
+    let mut nev = k + nzero;
+    loop {
+        let result = lobpcg(op, nev, options.tol, options.max_iter);
+        if !result.converged {
+            return Err(EnmError::NotConverged {
+                iterations: result.iterations,
+            });
+        }
+        let scale = scale.unwrap_or_else(|| result.values.iter().fold(0.0f64, |acc, x| acc.max(x.abs())));
+        let threshold = tolerance * scale;
+        let modes = result
+            .values
+            .into_iter()
+            .zip(result.vectors.column_iter().map(|x| x.as_slice().to_owned()))
+            .filter(|x| x.0.abs() > threshold)
+            .take(k)
+            .collect_vec();
+        // more zero modes than expected for disconnected network
+        if modes.len() < k && nev < op.dim() {
+            nev += k - modes.len();
+            continue;
+        }
+        return Ok(modes);
+    }
+}
+
+impl AnisotropicNetworkModel {
+    /// Calculates the lowest `k` normal modes of the Hessian matrix
+    /// `hessian` (dense or sparse) using an iterative eigensolver,
+    /// with translational and rotational modes removed. The layout of
+    /// the results is the same as `calculate_normal_modes`.
+    pub fn calculate_lowest_modes(
+        &self,
+        hessian: &impl SymmetricOperator,
+        k: usize,
+        options: &LobpcgOptions,
+    ) -> Result<Vec<(f64, Vec<f64>)>, EnmError> {
+        let mut modes = calculate_lowest_modes(hessian, k, 6, self.zero_tolerance, options)?;
+        if self.mass_weighted {
+            for (v, _) in modes.iter_mut() {
+                *v = eigenvalue_to_frequency(*v);
+            }
+        }
+        Ok(modes)
+    }
+}
+
+impl GaussianNetworkModel {
+    /// Calculates the lowest `k` normal modes of the Kirchhoff matrix
+    /// `kirchhoff` using an iterative eigensolver, with zero modes
+    /// removed.
+    pub fn calculate_lowest_modes(
+        &self,
+        kirchhoff: &impl SymmetricOperator,
+        k: usize,
+        options: &LobpcgOptions,
+    ) -> Result<Vec<(f64, Vec<f64>)>, EnmError> {
+        calculate_lowest_modes(kirchhoff, k, 1, self.zero_tolerance, options)
+    }
+}
+
+#[test]
+fn test_lobpcg() {
+    use approx::*;
+
+    // a coarse-grained helix of 40 atoms
+    let coords: Vec<[f64; 3]> = (0..40)
+        .map(|i| {
+            let t = i as f64 * 100f64.to_radians();
+            [2.3 * t.cos(), 2.3 * t.sin(), 1.5 * i as f64]
+        })
+        .collect();
+
+    let anm = AnisotropicNetworkModel {
+        cutoff: 10.0,
+        ..Default::default()
+    };
+    let hessian = anm.build_sparse_hessian_matrix(&coords, None);
+    let options = LobpcgOptions::default();
+    let modes = anm.calculate_lowest_modes(&hessian, 5, &options).unwrap();
+    let modes_ref = anm.calculate_normal_modes(hessian.to_dense());
+    assert_eq!(modes.len(), 5);
+    for (mode, mode_ref) in modes.iter().zip(&modes_ref) {
+        assert_relative_eq!(mode.0, mode_ref.0, epsilon = 1E-6);
+        // eigenvectors are determined up to the sign
+        let overlap: f64 = mode.1.iter().zip(&mode_ref.1).map(|(a, b)| a * b).sum();
+        assert_relative_eq!(overlap.abs(), 1.0, epsilon = 1E-6);
+    }
+
+    // a longer helix far from the dense fallback
+    let coords: Vec<[f64; 3]> = (0..60)
+        .map(|i| {
+            let t = i as f64 * 100f64.to_radians();
+            [2.3 * t.cos(), 2.3 * t.sin(), 1.5 * i as f64]
+        })
+        .collect();
+    let hessian = anm.build_sparse_hessian_matrix(&coords, None);
+    let result = lobpcg(&hessian, 8, 1E-8, 10000);
+    assert!(result.converged);
+    assert!(result.iterations > 10);
+    let (values_ref, _) = sorted_symmetric_eigen(hessian.to_dense());
+    for (v, v_ref) in result.values.iter().zip(&values_ref) {
+        assert_relative_eq!(v, v_ref, epsilon = 1E-6);
+    }
+
+    // too few iterations
+    let options = LobpcgOptions { tol: 1E-8, max_iter: 3 };
+    let e = anm.calculate_lowest_modes(&hessian, 2, &options).unwrap_err();
+    assert_eq!(e, EnmError::NotConverged { iterations: 3 });
+}
+// e0a4c6d3 ends here
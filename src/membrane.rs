@@ -0,0 +1,254 @@
+// [[file:../enm.note::f26b8c05][f26b8c05]]
+use gut::prelude::*;
+use nalgebra::DMatrix;
+use vecfx::*;
+
+use crate::enm::{atom_masses, calculate_normal_modes};
+use crate::modes::{NetworkModel, NormalModes};
+use crate::neighbor::find_contacts;
+use crate::{AnisotropicNetworkModel, EnmError};
+
+/// Lipid bilayer slab represented by a lattice of pseudo-atoms around a
+/// membrane protein.
+///
+/// # References
+///
+/// - Lezon, T. R.; Bahar, I. Biophysical Journal 2012, 102 (6), 1331–1340. <https://doi.org/10.1016/j.bpj.2012.02.028>
+#[derive(Debug, Clone)]
+pub struct Membrane {
+    /// Thickness of the slab along membrane normal
+    pub thickness: f64,
+    /// Membrane normal, not necessarily normalized
+    pub normal: [f64; 3],
+    /// Center of the slab
+    pub center: [f64; 3],
+    /// Spacing of the cubic lattice
+    pub spacing: f64,
+    /// Lateral extent of the slab beyond the protein
+    pub padding: f64,
+    /// Lattice points closer than this to any protein atom are removed
+    pub exclusion: f64,
+}
+
+impl Default for Membrane {
+    fn default() -> Self {
+        Self {
+            thickness: 30.0,
+            normal: [0.0, 0.0, 1.0],
+            center: [0.0; 3],
+            spacing: 5.0,
+            padding: 20.0,
+            exclusion: 4.0,
+        }
+    }
+}
+
+impl Membrane {
+    /// Check parameters of the slab.
+    pub fn validate(&self) -> Result<(), EnmError> {
+        let norm = self.normal.iter().map(|x| x * x).sum::<f64>().sqrt();
+        let center = self.center.iter().copied().find(|x| !x.is_finite()).unwrap_or(0.0);
+        for (parameter, value, valid) in [
+            ("normal", norm, norm.is_finite() && norm > 0.0),
+            ("center", center, center.is_finite()),
+            ("thickness", self.thickness, self.thickness.is_finite() && self.thickness >= 0.0),
+            ("spacing", self.spacing, self.spacing.is_finite() && self.spacing > 0.0),
+            ("padding", self.padding, self.padding.is_finite() && self.padding >= 0.0),
+            ("exclusion", self.exclusion, self.exclusion.is_finite() && self.exclusion > 0.0),
+        ] {
+            if !valid {
+                return Err(EnmError::InvalidMembrane { parameter, value });
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate Cartesian coordinates of membrane pseudo-atoms around
+    /// protein atoms at `coords`. Returns an error for invalid
+    /// parameters.
+    pub fn build_lattice(&self, coords: &[[f64; 3]]) -> Result<Vec<[f64; 3]>, EnmError> {
+        self.validate()?;
+        if coords.is_empty() {
+            return Ok(vec![]);
+        }
+        // orthonormal frame with z along membrane normal
+        let ez = Vector3f::from(self.normal).normalize();
+        let trial = if ez.x.abs() < 0.9 { Vector3f::x() } else { Vector3f::y() };
+        let ex = trial.cross(&ez).normalize();
+        let ey = ez.cross(&ex);
+        let center = Vector3f::from(self.center);
+
+        // lateral extent of the protein in membrane plane
+        let (mut xmin, mut xmax) = (f64::INFINITY, f64::NEG_INFINITY);
+        let (mut ymin, mut ymax) = (f64::INFINITY, f64::NEG_INFINITY);
+        for p in coords {
+            let r = Vector3f::from(*p) - center;
+            xmin = xmin.min(r.dot(&ex));
+            xmax = xmax.max(r.dot(&ex));
+            ymin = ymin.min(r.dot(&ey));
+            ymax = ymax.max(r.dot(&ey));
+        }
+        let grid = |lo: f64, hi: f64| {
+            let (lo, hi) = (lo - self.padding, hi + self.padding);
+            let n = ((hi - lo) / self.spacing).floor() as usize;
+            (0..=n).map(move |k| lo + k as f64 * self.spacing)
+        };
+        let nz = (self.thickness / self.spacing).floor() as usize;
+        let z0 = -(nz as f64) * self.spacing / 2.0;
+
+        let lattice = grid(xmin, xmax)
+            .cartesian_product(grid(ymin, ymax).collect_vec())
+            .cartesian_product((0..=nz).map(|k| z0 + k as f64 * self.spacing).collect_vec())
+            .map(|((x, y), z)| (center + x * ex + y * ey + z * ez).into())
+            .collect_vec();
+
+        // remove lattice points overlapping with the protein
+        let mut points = coords.to_vec();
+        points.extend(&lattice);
+        let nprotein = coords.len();
+        let excluded: std::collections::HashSet<_> = find_contacts(&points, self.exclusion)
+            .into_iter()
+            .filter(|c| c.i >= nprotein && c.j < nprotein)
+            .map(|c| c.i - nprotein)
+            .collect();
+        let lattice = lattice
+            .into_iter()
+            .enumerate()
+            .filter_map(|(k, p)| (!excluded.contains(&k)).then_some(p))
+            .collect();
+        Ok(lattice)
+    }
+}
+
+impl AnisotropicNetworkModel {
+    /// Build effective Hessian matrix (3N*3N) of protein atoms at
+    /// `coords` embedded in `membrane`, with membrane pseudo-atoms
+    /// coupled through the same springs and then reduced out by Schur
+    /// complement. Returns an error for invalid `membrane`.
+    pub fn build_membrane_hessian_matrix(&self, coords: &[[f64; 3]], membrane: &Membrane) -> Result<DMatrix<f64>, EnmError> {
+        let (model, all) = self.membrane_system(coords, membrane)?;
+        let n = coords.len();
+        let system = (0..n).collect_vec();
+        let environment = (n..all.len()).collect_vec();
+        Ok(model.build_reduced_hessian_matrix(&all, None, &system, &environment))
+    }
+
+    /// Calculates normal modes of protein atoms at `coords` embedded in
+    /// `membrane` (exANM). Returns an error for invalid `membrane`.
+    pub fn membrane_normal_modes(&self, coords: &[[f64; 3]], membrane: &Membrane) -> Result<NormalModes, EnmError> {
+        let hessian = self.build_membrane_hessian_matrix(coords, membrane)?;
+        let modes = calculate_normal_modes(hessian, self.zero_tolerance);
+        let masses = self.mass_weighted.then(|| atom_masses(coords.len(), None));
+        Ok(NormalModes::new(NetworkModel::Anisotropic(self.clone()), coords.len(), masses, modes))
+    }
+
+    /// Returns the model and coordinates of protein atoms at `coords`
+    /// followed by membrane pseudo-atoms. Each pseudo-atom is put in its
+    /// own chain without bonded springs.
+    fn membrane_system(&self, coords: &[[f64; 3]], membrane: &Membrane) -> Result<(Self, Vec<[f64; 3]>), EnmError> {
+        let lattice = membrane.build_lattice(coords)?;
+        debug!("membrane: {} pseudo-atoms", lattice.len());
+        let n = coords.len();
+        let mut model = self.clone();
+        if let Some(bonded) = model.bonded.as_mut() {
+            let topology = &mut bonded.topology;
+            for ntopology in [topology.chains.len(), topology.residues.len()] {
+                if ntopology != n {
+                    return Err(EnmError::TopologyMismatch { natoms: n, ntopology });
+                }
+            }
+            let chain = topology.chains.iter().max().map_or(0, |c| c + 1);
+            topology.chains.extend(chain..chain + lattice.len());
+            topology.residues.extend(vec![0; lattice.len()]);
+        }
+        let mut all = coords.to_vec();
+        all.extend(lattice);
+        Ok((model, all))
+    }
+}
+
+#[test]
+fn test_membrane() -> Result<()> {
+    // a transmembrane helix along z
+    let coords: Vec<[f64; 3]> = (0..20)
+        .map(|i| {
+            let t = i as f64 * 100f64.to_radians();
+            [2.3 * t.cos(), 2.3 * t.sin(), 1.5 * i as f64 - 15.0]
+        })
+        .collect();
+
+    let membrane = Membrane {
+        thickness: 10.0,
+        spacing: 4.0,
+        padding: 8.0,
+        ..Default::default()
+    };
+    let lattice = membrane.build_lattice(&coords)?;
+    assert!(!lattice.is_empty());
+    // the slab covers the protein only, regardless of its center
+    let shifted = Membrane {
+        center: [50.0, 50.0, 0.0],
+        ..membrane.clone()
+    };
+    assert_eq!(shifted.build_lattice(&coords)?.len(), lattice.len());
+    for p in &lattice {
+        assert!(p[2].abs() <= 5.0 + 1E-8);
+        assert!(coords.iter().all(|q| (0..3).map(|k| (p[k] - q[k]).powi(2)).sum::<f64>() >= 16.0));
+    }
+
+    let anm = AnisotropicNetworkModel {
+        cutoff: 8.0,
+        ..Default::default()
+    };
+    let hessian = anm.build_membrane_hessian_matrix(&coords, &membrane)?;
+    assert_eq!(hessian.shape(), (60, 60));
+    let modes = anm.membrane_normal_modes(&coords, &membrane)?;
+    assert_eq!(modes.natoms(), 20);
+    assert_eq!(modes.nmodes(), 60 - 6);
+
+    // bonded springs along the protein chain only
+    use crate::spring::{BondedSprings, Topology};
+    let topology = Topology {
+        chains: vec![0; 20],
+        residues: (1..=20).collect(),
+    };
+    let anm_bonded = AnisotropicNetworkModel {
+        bonded: Some(BondedSprings {
+            topology: topology.clone(),
+            gammas: vec![10.0],
+        }),
+        ..anm.clone()
+    };
+    let modes_bonded = anm_bonded.membrane_normal_modes(&coords, &membrane)?;
+    assert_eq!(modes_bonded.nmodes(), 60 - 6);
+    assert!(modes_bonded.eigenvalues()[0] > modes.eigenvalues()[0]);
+    let anm_bonded = AnisotropicNetworkModel {
+        bonded: Some(BondedSprings {
+            topology: topology.subset(&[0, 1, 2]),
+            gammas: vec![10.0],
+        }),
+        ..anm.clone()
+    };
+    assert_eq!(
+        anm_bonded.membrane_normal_modes(&coords, &membrane).unwrap_err(),
+        EnmError::TopologyMismatch { natoms: 20, ntopology: 3 }
+    );
+
+    // invalid membrane parameters
+    let invalid = Membrane {
+        normal: [0.0; 3],
+        ..membrane.clone()
+    };
+    assert!(matches!(invalid.build_lattice(&coords), Err(EnmError::InvalidMembrane { parameter: "normal", .. })));
+    let invalid = Membrane {
+        exclusion: 0.0,
+        ..membrane.clone()
+    };
+    assert!(matches!(
+        anm.build_membrane_hessian_matrix(&coords, &invalid),
+        Err(EnmError::InvalidMembrane { parameter: "exclusion", .. })
+    ));
+
+    Ok(())
+}
+// f26b8c05 ends here
@@ -0,0 +1,223 @@
+// [[file:../enm.note::c81f2e5b][c81f2e5b]]
+use gut::prelude::*;
+use nalgebra::DMatrix;
+use serde::{Deserialize, Serialize};
+use vecfx::*;
+
+use crate::enm::{atom_masses, calculate_normal_modes, eigenvalue_to_frequency};
+use crate::lobpcg::{calculate_lowest_modes, LobpcgOptions};
+use crate::{AnisotropicNetworkModel, EnmError, GaussianNetworkModel};
+
+/// The elastic network model and its parameters used for computing
+/// normal modes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NetworkModel {
+    Anisotropic(AnisotropicNetworkModel),
+    Gaussian(GaussianNetworkModel),
+}
+
+/// Normal modes computed from an elastic network model, sorted by
+/// eigenvalue in ascending order with zero modes removed.
+#[derive(Debug, Clone)]
+pub struct NormalModes {
+    model: NetworkModel,
+    natoms: usize,
+    /// atom masses used for the mass weighted Hessian matrix
+    masses: Option<Vec<f64>>,
+    eigenvalues: Vec<f64>,
+    /// eigenvectors stored as columns
+    eigenvectors: DMatrix<f64>,
+}
+
+impl NormalModes {
+    /// Construct from eigenpairs `modes` for `natoms` atoms in the
+    /// layout of `calculate_normal_modes`, which must be eigenvalues
+    /// rather than frequencies.
+    pub fn new(model: NetworkModel, natoms: usize, masses: Option<Vec<f64>>, modes: Vec<(f64, Vec<f64>)>) -> Self {
+        let dim = match model {
+            NetworkModel::Anisotropic(_) => 3 * natoms,
+            NetworkModel::Gaussian(_) => natoms,
+        };
+        if let Some(masses) = &masses {
+            assert_eq!(masses.len(), natoms, "invalid number of masses");
+        }
+        let eigenvalues = modes.iter().map(|x| x.0).collect_vec();
+        let mut eigenvectors = DMatrix::zeros(dim, modes.len());
+        for (k, (_, v)) in modes.iter().enumerate() {
+            assert_eq!(v.len(), dim, "invalid eigenvector size");
+            eigenvectors.column_mut(k).copy_from_slice(v);
+        }
+
+        Self {
+            model,
+            natoms,
+            masses,
+            eigenvalues,
+            eigenvectors,
+        }
+    }
+
+    /// The model parameters used for computing the modes.
+    pub fn model(&self) -> &NetworkModel {
+        &self.model
+    }
+
+    /// The number of atoms N.
+    pub fn natoms(&self) -> usize {
+        self.natoms
+    }
+
+    /// The number of modes.
+    pub fn nmodes(&self) -> usize {
+        self.eigenvalues.len()
+    }
+
+    /// The number of degrees of freedom per atom: 3 for ANM and 1 for
+    /// GNM.
+    pub fn dof_per_atom(&self) -> usize {
+        self.eigenvectors.nrows() / self.natoms.max(1)
+    }
+
+    /// Returns true if the modes are computed from a mass weighted
+    /// Hessian matrix.
+    pub fn is_mass_weighted(&self) -> bool {
+        matches!(&self.model, NetworkModel::Anisotropic(anm) if anm.mass_weighted)
+    }
+
+    /// Atom masses used for mass weighting, if any.
+    pub fn masses(&self) -> Option<&[f64]> {
+        self.masses.as_deref()
+    }
+
+    /// Eigenvalues in ascending order.
+    pub fn eigenvalues(&self) -> &[f64] {
+        &self.eigenvalues
+    }
+
+    /// Vibrational frequencies in cm-1, available only for modes of mass
+    /// weighted Hessian matrix.
+    pub fn frequencies(&self) -> Option<Vec<f64>> {
+        if self.is_mass_weighted() {
+            Some(self.eigenvalues.iter().map(|&v| eigenvalue_to_frequency(v)).collect())
+        } else {
+            None
+        }
+    }
+
+    /// Eigenvectors as columns of matrix (3N*M for ANM, N*M for GNM).
+    pub fn eigenvectors(&self) -> &DMatrix<f64> {
+        &self.eigenvectors
+    }
+
+    /// Returns the eigenvalue and eigenvector of mode `k`.
+    pub fn mode(&self, k: usize) -> (f64, &[f64]) {
+        let dim = self.eigenvectors.nrows();
+        (self.eigenvalues[k], &self.eigenvectors.as_slice()[k * dim..(k + 1) * dim])
+    }
+
+    /// Returns Cartesian displacements of atoms along mode `k`. For mass
+    /// weighted modes the eigenvector is scaled by 1/sqrt(m) for each
+    /// atom. Panics for GNM modes which have no directions.
+    pub fn displacements(&self, k: usize) -> Vec<[f64; 3]> {
+        assert_eq!(self.dof_per_atom(), 3, "displacements are only available for ANM modes");
+        let (_, v) = self.mode(k);
+        v.chunks_exact(3)
+            .enumerate()
+            .map(|(i, d)| {
+                let w = self.masses().map(|m| 1.0 / m[i].sqrt()).unwrap_or(1.0);
+                [d[0] * w, d[1] * w, d[2] * w]
+            })
+            .collect()
+    }
+
+    /// Iterate over all modes as (eigenvalue, eigenvector) pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (f64, &[f64])> + '_ {
+        (0..self.nmodes()).map(move |k| self.mode(k))
+    }
+}
+
+impl<'a> IntoIterator for &'a NormalModes {
+    type Item = (f64, &'a [f64]);
+    type IntoIter = Box<dyn Iterator<Item = (f64, &'a [f64])> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl AnisotropicNetworkModel {
+    /// Calculates normal modes for Cartesian `coords` of N atoms by
+    /// building and diagonalizing the Hessian matrix.
+    pub fn normal_modes<'a>(&self, coords: &[[f64; 3]], masses: impl Into<Option<&'a [f64]>>) -> NormalModes {
+        let masses = masses.into();
+        let hessian = self.build_hessian_matrix(coords, masses);
+        let modes = calculate_normal_modes(hessian, self.zero_tolerance);
+        let masses = self.mass_weighted.then(|| atom_masses(coords.len(), masses));
+        NormalModes::new(NetworkModel::Anisotropic(self.clone()), coords.len(), masses, modes)
+    }
+
+    /// Calculates the lowest `k` normal modes for Cartesian `coords` of
+    /// N atoms using sparse Hessian matrix and iterative eigensolver
+    /// with `options`. Returns an error if the eigensolver does not
+    /// converge.
+    pub fn lowest_normal_modes<'a>(
+        &self,
+        coords: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+        k: usize,
+        options: &LobpcgOptions,
+    ) -> Result<NormalModes, EnmError> {
+        let masses = masses.into();
+        let hessian = self.build_sparse_hessian_matrix(coords, masses);
+        let modes = calculate_lowest_modes(&hessian, k, 6, self.zero_tolerance, options)?;
+        let masses = self.mass_weighted.then(|| atom_masses(coords.len(), masses));
+        Ok(NormalModes::new(NetworkModel::Anisotropic(self.clone()), coords.len(), masses, modes))
+    }
+}
+
+impl GaussianNetworkModel {
+    /// Calculates normal modes for Cartesian `coords` of N atoms by
+    /// building and diagonalizing the Kirchhoff matrix.
+    pub fn normal_modes(&self, coords: &[[f64; 3]]) -> NormalModes {
+        let kirchhoff = self.build_kirchhoff_matrix(coords);
+        let modes = calculate_normal_modes(kirchhoff, self.zero_tolerance);
+        NormalModes::new(NetworkModel::Gaussian(self.clone()), coords.len(), None, modes)
+    }
+}
+
+#[test]
+fn test_normal_modes() {
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    let anm = AnisotropicNetworkModel::default();
+    let modes = anm.normal_modes(&coords, None);
+    assert_eq!(modes.natoms(), 8);
+    assert_eq!(modes.nmodes(), 18);
+    assert_eq!(modes.dof_per_atom(), 3);
+    assert!(modes.frequencies().is_none());
+    let (value, vector) = modes.mode(0);
+    assert_relative_eq!(value, 0.47256486306316137, epsilon = 1E-4);
+    assert_relative_eq!(vector[2], -0.36812, epsilon = 1E-4);
+    let disp = modes.displacements(0);
+    assert_relative_eq!(disp[0][2], -0.36812, epsilon = 1E-4);
+    assert_eq!(modes.iter().count(), 18);
+
+    let anm = AnisotropicNetworkModel {
+        mass_weighted: true,
+        ..Default::default()
+    };
+    let modes = anm.normal_modes(&coords, None);
+    let freqs = modes.frequencies().unwrap();
+    let freqs_ref = anm.calculate_normal_modes(anm.build_hessian_matrix(&coords, None));
+    for (f, (f_ref, _)) in freqs.iter().zip(&freqs_ref) {
+        assert_relative_eq!(*f, *f_ref, epsilon = 1E-6);
+    }
+
+    let gnm = GaussianNetworkModel::default();
+    let modes = gnm.normal_modes(&coords);
+    assert_eq!(modes.nmodes(), 7);
+    assert_eq!(modes.dof_per_atom(), 1);
+}
+// c81f2e5b ends here
@@ -0,0 +1,92 @@
+// [[file:../enm.note::b52d8e17][b52d8e17]]
+use std::collections::HashMap;
+
+use vecfx::*;
+
+/// A spring in the elastic network between atom `i` and atom `j`
+/// (`i` > `j`) separated by `distance`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    pub i: usize,
+    pub j: usize,
+    pub distance: f64,
+}
+
+/// Find all atom pairs in `coords` separated by less than `cutoff`
+/// using a cell list, which scales linearly with the number of
+/// atoms. Returns contacts sorted by (`i`, `j`). All pairs are
+/// returned if `cutoff` is infinite.
+pub fn find_contacts(coords: &[[f64; 3]], cutoff: f64) -> Vec<Contact> {
+    if !cutoff.is_finite() {
+        return find_contacts_brute_force(coords, cutoff);
+    }
+    assert!(cutoff > 0.0, "invalid cutoff: {cutoff}");
+
+    // assign atoms into cubic cells with edge length of `cutoff`
+    let cell_index = |p: &[f64; 3]| -> [i64; 3] { p.map(|x| (x / cutoff).floor() as i64) };
+    let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    for (i, p) in coords.iter().enumerate() {
+        cells.entry(cell_index(p)).or_default().push(i);
+    }
+
+    // only pairs in the same or adjacent cells can be within cutoff
+    let cutoff2 = cutoff.powi(2);
+    let mut contacts = vec![];
+    for (i, p) in coords.iter().enumerate() {
+        let [cx, cy, cz] = cell_index(p);
+        let ri: Vector3f = (*p).into();
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(atoms) = cells.get(&[cx + dx, cy + dy, cz + dz]) {
+                        for &j in atoms.iter().filter(|&&j| j < i) {
+                            let rj: Vector3f = coords[j].into();
+                            let dist2 = (rj - ri).norm_squared();
+                            if dist2 < cutoff2 {
+                                contacts.push(Contact { i, j, distance: dist2.sqrt() });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    contacts.sort_by_key(|c| (c.i, c.j));
+    contacts
+}
+
+/// Find contacts by checking all atom pairs.
+fn find_contacts_brute_force(coords: &[[f64; 3]], cutoff: f64) -> Vec<Contact> {
+    let cutoff2 = cutoff.powi(2);
+    let mut contacts = vec![];
+    for (i, pi) in coords.iter().enumerate() {
+        let ri: Vector3f = (*pi).into();
+        for (j, pj) in coords[..i].iter().enumerate() {
+            let rj: Vector3f = (*pj).into();
+            let dist2 = (rj - ri).norm_squared();
+            if dist2 < cutoff2 {
+                contacts.push(Contact { i, j, distance: dist2.sqrt() });
+            }
+        }
+    }
+    contacts
+}
+
+#[test]
+fn test_find_contacts() {
+    // a slightly distorted grid of atoms
+    let coords: Vec<[f64; 3]> = (0..125)
+        .map(|k| {
+            let (x, y, z) = (k % 5, (k / 5) % 5, k / 25);
+            [x as f64 * 1.9 - 3.1, y as f64 * 2.1 + 0.3 * (k as f64).sin(), z as f64 * 2.0]
+        })
+        .collect();
+
+    for cutoff in [1.5, 2.5, 4.0, 7.3] {
+        let contacts = find_contacts(&coords, cutoff);
+        let expected = find_contacts_brute_force(&coords, cutoff);
+        assert_eq!(contacts, expected);
+    }
+    assert_eq!(find_contacts(&coords, f64::INFINITY).len(), 125 * 124 / 2);
+}
+// b52d8e17 ends here
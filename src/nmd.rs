@@ -0,0 +1,101 @@
+// [[file:../enm.note::7d3e9b52][7d3e9b52]]
+use std::fmt::Write as _;
+
+use gut::prelude::*;
+
+use crate::modes::NormalModes;
+use crate::pdb::AtomRecord;
+
+impl NormalModes {
+    /// Format the lowest `nmodes` modes for atoms at `coords` in NMD
+    /// format of VMD Normal Mode Wizard (NMWiz). Atom names, residue
+    /// names, chain ids, residue numbers and B-factors are taken from
+    /// `atoms` if available. Mode vectors are written as normalized
+    /// Cartesian displacements, scaled by 1/sqrt(λ). GNM modes have no
+    /// directions and cannot be written.
+    ///
+    /// # References
+    ///
+    /// - Bakan, A. et al. Bioinformatics 2011, 27 (11), 1575–1577. <https://doi.org/10.1093/bioinformatics/btr168>
+    pub fn to_nmd_string(&self, title: &str, coords: &[[f64; 3]], atoms: Option<&[AtomRecord]>, nmodes: usize) -> Result<String> {
+        ensure!(self.dof_per_atom() == 3, "NMD format requires ANM modes with 3D vectors");
+        ensure!(coords.len() == self.natoms(), "invalid number of atoms: {} != {}", coords.len(), self.natoms());
+        if let Some(atoms) = atoms {
+            ensure!(atoms.len() == self.natoms(), "invalid number of atom records: {}", atoms.len());
+        }
+
+        let mut s = String::new();
+        writeln!(s, "title {}", title.replace(char::is_whitespace, "_"))?;
+        if let Some(atoms) = atoms {
+            writeln!(s, "names {}", atoms.iter().map(|a| &a.name).join(" "))?;
+            writeln!(s, "resnames {}", atoms.iter().map(|a| &a.resname).join(" "))?;
+            // blank chain identifiers are not allowed
+            let chains = atoms.iter().map(|a| if a.chain.is_empty() { "X" } else { a.chain.as_str() });
+            writeln!(s, "chainids {}", chains.format(" "))?;
+            writeln!(s, "resids {}", atoms.iter().map(|a| a.resseq).join(" "))?;
+            writeln!(s, "betas {}", atoms.iter().map(|a| format!("{:.2}", a.bfactor)).join(" "))?;
+        }
+        writeln!(s, "coordinates {}", coords.iter().flatten().map(|x| format!("{x:.3}")).join(" "))?;
+        for k in 0..nmodes.min(self.nmodes()) {
+            let d = self.displacements(k);
+            let norm = d.iter().flatten().map(|x| x * x).sum::<f64>().sqrt();
+            let scale = norm / self.eigenvalues()[k].sqrt();
+            let vector = d.iter().flatten().map(|x| format!("{:.3}", x / norm)).join(" ");
+            writeln!(s, "mode {} {scale:.2} {vector}", k + 1)?;
+        }
+        Ok(s)
+    }
+
+    /// Write modes in NMD format to file at `path`. See also
+    /// `to_nmd_string`.
+    pub fn write_nmd(&self, path: impl AsRef<Path>, coords: &[[f64; 3]], atoms: Option<&[AtomRecord]>, nmodes: usize) -> Result<()> {
+        let path = path.as_ref();
+        let title = path.file_stem().and_then(|x| x.to_str()).unwrap_or("enm");
+        let s = self.to_nmd_string(title, coords, atoms, nmodes)?;
+        std::fs::write(path, s).with_context(|| format!("failed to write {path:?}"))
+    }
+}
+
+#[test]
+fn test_nmd() -> Result<()> {
+    use crate::pdb::Structure;
+    use crate::{AnisotropicNetworkModel, GaussianNetworkModel};
+    use approx::*;
+
+    let pdb = "\
+ATOM      1  CA  MET A   1      -1.723   1.188   1.856  1.00  9.67           C
+ATOM      2  CA  GLN A   2      -3.404   0.600   1.768  1.00 10.38           C
+ATOM      3  CA  ILE A   3      -4.674  -1.113   0.601  1.00  9.62           C
+ATOM      4  CA  PHE A   4      -2.967  -0.682   0.545  1.00  9.62           C
+ATOM      5  CA  VAL A   5      -3.094   2.295   1.392  1.00  9.27           C
+ATOM      6  CA  LYS B   6      -2.510   1.079   0.261  1.00  9.07           C
+ATOM      7  CA  THR B   7      -4.253   0.540   0.157  1.00  9.07           C
+ATOM      8  CA  LEU B   8      -3.857  -0.766  -0.992  1.00  9.00           C
+";
+    let structure = Structure::from_pdb_str(pdb)?;
+    let coords = structure.coords();
+    let anm = AnisotropicNetworkModel::default();
+    let modes = anm.normal_modes(&coords, None);
+
+    let nmd = modes.to_nmd_string("test protein", &coords, Some(structure.atoms()), 3)?;
+    let lines = nmd.lines().collect_vec();
+    assert_eq!(lines[0], "title test_protein");
+    assert_eq!(lines[3], "chainids A A A A A B B B");
+    assert_eq!(lines.iter().filter(|l| l.starts_with("mode ")).count(), 3);
+    let tokens = lines[7].split_whitespace().collect_vec();
+    assert_eq!(tokens[..2], ["mode", "1"]);
+    assert_eq!(tokens.len(), 3 + 24);
+    let scale: f64 = tokens[2].parse()?;
+    assert_relative_eq!(scale, 1.0 / modes.eigenvalues()[0].sqrt(), epsilon = 1E-2);
+
+    // without atom records
+    let nmd = modes.to_nmd_string("test", &coords, None, 100)?;
+    assert!(nmd.lines().nth(1).unwrap().starts_with("coordinates "));
+    assert_eq!(nmd.lines().count(), 2 + modes.nmodes());
+
+    let gnm = GaussianNetworkModel::default();
+    assert!(gnm.normal_modes(&coords).to_nmd_string("test", &coords, None, 3).is_err());
+
+    Ok(())
+}
+// 7d3e9b52 ends here
@@ -0,0 +1,119 @@
+// [[file:../enm.note::0c6e3b97][0c6e3b97]]
+use gut::prelude::*;
+use nalgebra::DMatrix;
+use vecfx::*;
+
+use crate::NormalModes;
+
+/// A set of mode vectors, for comparing modes from `NormalModes` or
+/// `calculate_normal_modes`.
+pub trait ModeSet {
+    /// Mode vectors in ascending order of eigenvalues.
+    fn mode_vectors(&self) -> Vec<&[f64]>;
+}
+
+impl ModeSet for NormalModes {
+    fn mode_vectors(&self) -> Vec<&[f64]> {
+        self.iter().map(|(_, v)| v).collect()
+    }
+}
+
+impl ModeSet for [(f64, Vec<f64>)] {
+    fn mode_vectors(&self) -> Vec<&[f64]> {
+        self.iter().map(|(_, v)| v.as_slice()).collect()
+    }
+}
+
+impl ModeSet for Vec<(f64, Vec<f64>)> {
+    fn mode_vectors(&self) -> Vec<&[f64]> {
+        self.as_slice().mode_vectors()
+    }
+}
+
+fn dot(u: &[f64], v: &[f64]) -> f64 {
+    assert_eq!(u.len(), v.len(), "vector size mismatch");
+    u.iter().zip(v).map(|(a, b)| a * b).sum()
+}
+
+/// Overlap between two vectors `u` and `v`: |u·v| / (|u| |v|).
+pub fn overlap(u: &[f64], v: &[f64]) -> f64 {
+    dot(u, v).abs() / (dot(u, u) * dot(v, v)).sqrt()
+}
+
+/// Overlaps of `target` vector with each of the first `k` modes in
+/// `modes`.
+pub fn mode_overlaps(target: &[f64], modes: &(impl ModeSet + ?Sized), k: usize) -> Vec<f64> {
+    modes.mode_vectors().into_iter().take(k).map(|v| overlap(target, v)).collect()
+}
+
+/// Cumulative overlap of `target` vector with the first `k` modes in
+/// `modes`: the square root of the sum of squared overlaps.
+pub fn cumulative_overlap(target: &[f64], modes: &(impl ModeSet + ?Sized), k: usize) -> f64 {
+    mode_overlaps(target, modes, k).iter().map(|x| x * x).sum::<f64>().sqrt()
+}
+
+/// Matrix of inner products between the first `k` modes of `a` and `b`.
+fn inner_products(a: &(impl ModeSet + ?Sized), b: &(impl ModeSet + ?Sized), k: usize) -> DMatrix<f64> {
+    let va = a.mode_vectors().into_iter().take(k).collect_vec();
+    let vb = b.mode_vectors().into_iter().take(k).collect_vec();
+    DMatrix::from_fn(va.len(), vb.len(), |i, j| dot(va[i], vb[j]))
+}
+
+/// Root mean square inner product (RMSIP) between the subspaces of the
+/// first `k` modes of `a` and `b`.
+///
+/// # References
+///
+/// - Amadei, A. et al. Proteins 1999, 36 (4), 419–424. <https://doi.org/10.1002/(SICI)1097-0134(19990901)36:4<419::AID-PROT5>3.0.CO;2-U>
+pub fn rmsip(a: &(impl ModeSet + ?Sized), b: &(impl ModeSet + ?Sized), k: usize) -> f64 {
+    let m = inner_products(a, b, k);
+    let k = m.nrows().min(m.ncols());
+    (m.norm_squared() / k as f64).sqrt()
+}
+
+/// Principal angles in radians between the subspaces of the first `k`
+/// modes of `a` and `b`, in ascending order.
+pub fn principal_angles(a: &(impl ModeSet + ?Sized), b: &(impl ModeSet + ?Sized), k: usize) -> Vec<f64> {
+    let m = inner_products(a, b, k);
+    let svd = m.svd(false, false);
+    svd.singular_values
+        .iter()
+        .map(|s| s.min(1.0).acos())
+        .sorted_by_key(|x| OrderedFloat(*x))
+        .collect()
+}
+
+#[test]
+fn test_overlap() {
+    use crate::AnisotropicNetworkModel;
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    assert_relative_eq!(overlap(&[1.0, 0.0], &[-2.0, 0.0]), 1.0);
+    assert_relative_eq!(overlap(&[1.0, 1.0], &[0.0, 1.0]), 0.5f64.sqrt());
+
+    let anm = AnisotropicNetworkModel::default();
+    let modes = anm.normal_modes(&coords, None);
+    let pairs = anm.calculate_normal_modes(anm.build_hessian_matrix(&coords, None));
+
+    // identical subspaces
+    assert_relative_eq!(rmsip(&modes, &pairs, 5), 1.0, epsilon = 1E-8);
+    assert!(principal_angles(&modes, &pairs, 5).iter().all(|x| x.abs() < 1E-6));
+
+    // a mode is fully described by the modes
+    let (_, v) = modes.mode(2);
+    let overlaps = mode_overlaps(v, &modes, 5);
+    assert_relative_eq!(overlaps[2], 1.0, epsilon = 1E-8);
+    assert_relative_eq!(overlaps[0], 0.0, epsilon = 1E-8);
+    assert_relative_eq!(cumulative_overlap(v, &modes, 2), 0.0, epsilon = 1E-8);
+    assert_relative_eq!(cumulative_overlap(v, &modes, 3), 1.0, epsilon = 1E-8);
+
+    // orthogonal subspaces
+    let a = &pairs[..3];
+    let b = &pairs[3..6];
+    assert_relative_eq!(rmsip(a, b, 3), 0.0, epsilon = 1E-8);
+    let angles = principal_angles(a, b, 3);
+    assert_relative_eq!(angles[0], std::f64::consts::FRAC_PI_2, epsilon = 1E-6);
+}
+// 0c6e3b97 ends here
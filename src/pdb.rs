@@ -0,0 +1,604 @@
+// [[file:../enm.note::1e7b94d6][1e7b94d6]]
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use gut::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::spring::Topology;
+
+/// An atom record read from PDB or mmCIF file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtomRecord {
+    /// 1-based model number, for NMR files with multiple models
+    pub model: usize,
+    pub serial: usize,
+    pub name: String,
+    pub altloc: Option<char>,
+    pub resname: String,
+    pub chain: String,
+    pub resseq: i32,
+    pub icode: Option<char>,
+    pub position: [f64; 3],
+    pub occupancy: f64,
+    pub bfactor: f64,
+    pub element: String,
+    pub hetero: bool,
+}
+
+impl AtomRecord {
+    /// Atomic mass from the element symbol. Unknown elements are treated
+    /// as Carbon.
+    pub fn mass(&self) -> f64 {
+        match self.element.to_ascii_uppercase().as_str() {
+            "H" => 1.008,
+            "D" => 2.014,
+            "C" => 12.011,
+            "N" => 14.007,
+            "O" => 15.999,
+            "F" => 18.998,
+            "NA" => 22.990,
+            "MG" => 24.305,
+            "P" => 30.974,
+            "S" => 32.06,
+            "CL" => 35.45,
+            "K" => 39.098,
+            "CA" => 40.078,
+            "MN" => 54.938,
+            "FE" => 55.845,
+            "CO" => 58.933,
+            "NI" => 58.693,
+            "CU" => 63.546,
+            "ZN" => 65.38,
+            "SE" => 78.971,
+            "BR" => 79.904,
+            "I" => 126.904,
+            _ => 12.011,
+        }
+    }
+
+    /// Returns true for hydrogen atoms.
+    pub fn is_hydrogen(&self) -> bool {
+        matches!(self.element.to_ascii_uppercase().as_str(), "H" | "D")
+    }
+
+    /// Format as an ATOM or HETATM record in fixed-column PDB format.
+    pub fn to_pdb_line(&self) -> String {
+        let record = if self.hetero { "HETATM" } else { "ATOM" };
+        // names of one-letter elements start at column 14
+        let name = if self.name.len() < 4 && self.element.len() < 2 {
+            format!(" {:<3}", self.name)
+        } else {
+            format!("{:<4}", self.name)
+        };
+        let [x, y, z] = self.position;
+        format!(
+            "{:<6}{:>5} {}{}{:>3} {:1}{:>4}{}   {:8.3}{:8.3}{:8.3}{:6.2}{:6.2}          {:>2}",
+            record,
+            self.serial % 100000,
+            name,
+            self.altloc.unwrap_or(' '),
+            self.resname,
+            self.chain,
+            self.resseq,
+            self.icode.unwrap_or(' '),
+            x,
+            y,
+            z,
+            self.occupancy,
+            self.bfactor,
+            self.element,
+        )
+    }
+}
+
+/// Guess element symbol from PDB atom name, for files without element
+/// column.
+fn guess_element(name: &str) -> String {
+    let name = name.trim_start_matches(|c: char| c.is_ascii_digit());
+    name.chars().take_while(|c| c.is_ascii_alphabetic()).take(1).collect()
+}
+
+/// Types of atoms to be selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AtomSelection {
+    /// Alpha carbon atoms only
+    #[default]
+    #[serde(alias = "ca")]
+    CalphaOnly,
+    /// Backbone atoms: N, CA, C and O
+    Backbone,
+    /// All atoms except hydrogens
+    Heavy,
+    /// All atoms
+    All,
+}
+
+/// Selection of atoms for building elastic network models.
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub atoms: AtomSelection,
+    /// Select only atoms in these chains if not empty.
+    pub chains: Vec<String>,
+    /// Select only residues with sequence numbers in this range.
+    pub residues: Option<RangeInclusive<i32>>,
+    /// Preferred alternate location indicator. Only one location is
+    /// kept for atoms with alternate locations: the one with this
+    /// indicator if any, otherwise the one with the highest occupancy,
+    /// or the first one on ties.
+    pub altloc: Option<char>,
+    /// 1-based model number for files with multiple models.
+    pub model: usize,
+    /// Include HETATM records.
+    pub hetero: bool,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self {
+            atoms: AtomSelection::CalphaOnly,
+            chains: vec![],
+            residues: None,
+            altloc: None,
+            model: 1,
+            hetero: false,
+        }
+    }
+}
+
+impl Selection {
+    fn matches(&self, atom: &AtomRecord) -> bool {
+        let name = atom.name.as_str();
+        // exclude calcium ion named as CA, but keep CA atoms of modified
+        // residues in HETATM records, e.g. selenomethionine
+        let is_calcium = atom.element.eq_ignore_ascii_case("CA");
+        let atom_type_ok = match self.atoms {
+            AtomSelection::CalphaOnly => name == "CA" && !is_calcium,
+            AtomSelection::Backbone => matches!(name, "N" | "CA" | "C" | "O") && !is_calcium,
+            AtomSelection::Heavy => !atom.is_hydrogen(),
+            AtomSelection::All => true,
+        };
+        atom_type_ok
+            && atom.model == self.model
+            && (self.hetero || !atom.hetero)
+            && (self.chains.is_empty() || self.chains.contains(&atom.chain))
+            && self.residues.as_ref().map(|r| r.contains(&atom.resseq)).unwrap_or(true)
+    }
+
+    /// Returns true if alternate location `a` is preferred over `b` of
+    /// the same atom.
+    fn prefers(&self, a: &AtomRecord, b: &AtomRecord) -> bool {
+        match self.altloc {
+            Some(c) if a.altloc == Some(c) || b.altloc == Some(c) => a.altloc == Some(c) && b.altloc != Some(c),
+            _ => a.occupancy > b.occupancy,
+        }
+    }
+}
+
+/// A biomolecular structure read from PDB or mmCIF file.
+#[derive(Debug, Clone, Default)]
+pub struct Structure {
+    atoms: Vec<AtomRecord>,
+}
+
+impl Structure {
+    /// Construct from atom records.
+    pub fn from_atoms(atoms: Vec<AtomRecord>) -> Self {
+        Self { atoms }
+    }
+
+    /// Read structure from PDB, mmCIF or XYZ file at `path`, depending
+    /// on file extension.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+        let ext = path.extension().and_then(|x| x.to_str()).unwrap_or_default().to_lowercase();
+        let structure = match ext.as_str() {
+            "cif" | "mmcif" => Self::from_mmcif_str(&s),
+            "xyz" => Self::from_xyz_str(&s),
+            _ => Self::from_pdb_str(&s),
+        };
+        structure.with_context(|| format!("failed to parse {path:?}"))
+    }
+
+    /// Parse ATOM/HETATM records in PDB format.
+    pub fn from_pdb_str(s: &str) -> Result<Self> {
+        let mut atoms = vec![];
+        let mut model = 1;
+        for (i, line) in s.lines().enumerate() {
+            if let Some(number) = line.strip_prefix("MODEL") {
+                model = number.trim().parse().with_context(|| format!("invalid MODEL record at line {}", i + 1))?;
+            } else if line.starts_with("ATOM") || line.starts_with("HETATM") {
+                let atom = parse_pdb_atom(line, model).with_context(|| format!("invalid atom record at line {}", i + 1))?;
+                atoms.push(atom);
+            }
+        }
+        Ok(Self { atoms })
+    }
+
+    /// Parse the first frame in XYZ format. Each atom is named by its
+    /// element symbol and placed in its own residue.
+    pub fn from_xyz_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        let natoms: usize = lines.next().unwrap_or("").trim().parse().context("invalid number of atoms in XYZ")?;
+        let _title = lines.next();
+        let mut atoms = vec![];
+        for i in 0..natoms {
+            let line = lines.next().ok_or_else(|| anyhow!("expected {natoms} atoms, found {i}"))?;
+            let tokens = line.split_whitespace().collect_vec();
+            ensure!(tokens.len() >= 4, "invalid atom line {}: {line}", i + 3);
+            let mut position = [0.0; 3];
+            for k in 0..3 {
+                position[k] = tokens[k + 1].parse().with_context(|| format!("invalid coordinate at line {}", i + 3))?;
+            }
+            let element = tokens[0].trim_matches(|c: char| !c.is_ascii_alphabetic()).to_string();
+            atoms.push(AtomRecord {
+                model: 1,
+                serial: i + 1,
+                name: element.clone(),
+                altloc: None,
+                resname: "UNK".into(),
+                chain: "A".into(),
+                resseq: i as i32 + 1,
+                icode: None,
+                position,
+                occupancy: 1.0,
+                bfactor: 0.0,
+                element,
+                hetero: false,
+            });
+        }
+        Ok(Self { atoms })
+    }
+
+    /// Parse `_atom_site` loop in mmCIF format.
+    pub fn from_mmcif_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().map(|x| x.trim()).peekable();
+        let mut atoms = vec![];
+        while let Some(line) = lines.next() {
+            if line != "loop_" || !lines.peek().map(|x| x.starts_with("_atom_site.")).unwrap_or(false) {
+                continue;
+            }
+            let mut columns = vec![];
+            while let Some(header) = lines.peek().and_then(|x| x.strip_prefix("_atom_site.")) {
+                columns.push(header.trim().to_string());
+                lines.next();
+            }
+            let col = |name: &str| columns.iter().position(|x| x == name);
+            let get = |tokens: &[String], names: &[&str]| -> Option<String> {
+                names
+                    .iter()
+                    .filter_map(|&name| col(name))
+                    .map(|i| tokens[i].clone())
+                    .find(|x| x != "." && x != "?")
+            };
+            let required = |tokens: &[String], name: &str| -> Result<String> {
+                get(tokens, &[name]).ok_or_else(|| format_err!("missing _atom_site.{name}"))
+            };
+            while let Some(&line) = lines.peek() {
+                if line.starts_with('_') || line.starts_with("loop_") || line.starts_with('#') || line.is_empty() {
+                    break;
+                }
+                lines.next();
+                let tokens = tokenize_cif_line(line);
+                ensure!(tokens.len() == columns.len(), "invalid number of fields in atom_site: {line}");
+                let position = [
+                    required(&tokens, "Cartn_x")?.parse()?,
+                    required(&tokens, "Cartn_y")?.parse()?,
+                    required(&tokens, "Cartn_z")?.parse()?,
+                ];
+                let name = get(&tokens, &["auth_atom_id", "label_atom_id"]).ok_or_else(|| format_err!("missing atom name"))?;
+                let element = get(&tokens, &["type_symbol"]).unwrap_or_else(|| guess_element(&name));
+                let atom = AtomRecord {
+                    model: get(&tokens, &["pdbx_PDB_model_num"]).map(|x| x.parse()).transpose()?.unwrap_or(1),
+                    serial: get(&tokens, &["id"]).map(|x| x.parse()).transpose()?.unwrap_or(atoms.len() + 1),
+                    name,
+                    altloc: get(&tokens, &["label_alt_id"]).and_then(|x| x.chars().next()),
+                    resname: get(&tokens, &["auth_comp_id", "label_comp_id"]).unwrap_or_default(),
+                    chain: get(&tokens, &["auth_asym_id", "label_asym_id"]).unwrap_or_default(),
+                    resseq: get(&tokens, &["auth_seq_id", "label_seq_id"]).map(|x| x.parse()).transpose()?.unwrap_or(0),
+                    icode: get(&tokens, &["pdbx_PDB_ins_code"]).and_then(|x| x.chars().next()),
+                    position,
+                    occupancy: get(&tokens, &["occupancy"]).map(|x| x.parse()).transpose()?.unwrap_or(1.0),
+                    bfactor: get(&tokens, &["B_iso_or_equiv"]).map(|x| x.parse()).transpose()?.unwrap_or(0.0),
+                    element,
+                    hetero: get(&tokens, &["group_PDB"]).map(|x| x == "HETATM").unwrap_or(false),
+                };
+                atoms.push(atom);
+            }
+        }
+        Ok(Self { atoms })
+    }
+
+    /// All atom records.
+    pub fn atoms(&self) -> &[AtomRecord] {
+        &self.atoms
+    }
+
+    /// The number of atoms.
+    pub fn natoms(&self) -> usize {
+        self.atoms.len()
+    }
+
+    /// Returns a new structure with atoms matching `selection`, keeping
+    /// one location for atoms with alternate locations.
+    pub fn select(&self, selection: &Selection) -> Self {
+        let atoms = self.atoms.iter().filter(|a| selection.matches(a)).collect_vec();
+        let key = |a: &AtomRecord| (a.model, a.chain.clone(), a.resseq, a.icode, a.name.clone());
+        // the chosen location for each atom with alternate locations
+        let mut chosen: HashMap<_, usize> = HashMap::new();
+        for (k, a) in atoms.iter().enumerate().filter(|(_, a)| a.altloc.is_some()) {
+            let c = chosen.entry(key(a)).or_insert(k);
+            if selection.prefers(a, atoms[*c]) {
+                *c = k;
+            }
+        }
+        let atoms = atoms
+            .iter()
+            .enumerate()
+            .filter(|(k, a)| a.altloc.is_none() || chosen[&key(a)] == *k)
+            .map(|(_, &a)| a.clone())
+            .collect();
+        Self { atoms }
+    }
+
+    /// Cartesian coordinates of all atoms.
+    pub fn coords(&self) -> Vec<[f64; 3]> {
+        self.atoms.iter().map(|a| a.position).collect()
+    }
+
+    /// Atomic masses of all atoms.
+    pub fn masses(&self) -> Vec<f64> {
+        self.atoms.iter().map(|a| a.mass()).collect()
+    }
+
+    /// Experimental B-factors of all atoms.
+    pub fn bfactors(&self) -> Vec<f64> {
+        self.atoms.iter().map(|a| a.bfactor).collect()
+    }
+
+    /// Residue sequence numbers of all atoms, for mapping modes back
+    /// to residues.
+    pub fn residue_numbers(&self) -> Vec<i32> {
+        self.atoms.iter().map(|a| a.resseq).collect()
+    }
+
+    /// Block index of each atom with one block per residue, for
+    /// Rotation-Translation Block approximation.
+    pub fn residue_blocks(&self) -> Vec<usize> {
+        let mut blocks: HashMap<(&str, i32, Option<char>), usize> = HashMap::new();
+        self.atoms
+            .iter()
+            .map(|a| {
+                let n = blocks.len();
+                *blocks.entry((a.chain.as_str(), a.resseq, a.icode)).or_insert(n)
+            })
+            .collect()
+    }
+
+    /// Chain topology of all atoms, with chains indexed in order of
+    /// appearance.
+    pub fn topology(&self) -> Topology {
+        let chain_ids = self.atoms.iter().map(|a| a.chain.as_str()).unique().collect_vec();
+        let chains = self
+            .atoms
+            .iter()
+            .map(|a| chain_ids.iter().position(|&c| c == a.chain).unwrap())
+            .collect();
+        Topology {
+            chains,
+            residues: self.residue_numbers(),
+        }
+    }
+}
+
+/// Parse one ATOM or HETATM record in fixed-column PDB format.
+fn parse_pdb_atom(line: &str, model: usize) -> Result<AtomRecord> {
+    ensure!(line.is_ascii(), "non-ASCII characters in atom record");
+    ensure!(line.len() >= 54, "atom record is too short");
+    let field = |start: usize, end: usize| line.get(start..end.min(line.len())).unwrap_or("").trim();
+    let char_at = |i: usize| line.as_bytes().get(i).map(|&c| c as char).filter(|&c| c != ' ');
+
+    let name = field(12, 16).to_string();
+    let element = match field(76, 78) {
+        "" => guess_element(&name),
+        e => e.to_string(),
+    };
+    let atom = AtomRecord {
+        model,
+        serial: field(6, 11).parse().unwrap_or(0),
+        name,
+        altloc: char_at(16),
+        resname: field(17, 20).to_string(),
+        chain: field(21, 22).to_string(),
+        resseq: field(22, 26).parse().context("invalid residue number")?,
+        icode: char_at(26),
+        position: [
+            field(30, 38).parse().context("invalid x coordinate")?,
+            field(38, 46).parse().context("invalid y coordinate")?,
+            field(46, 54).parse().context("invalid z coordinate")?,
+        ],
+        occupancy: field(54, 60).parse().unwrap_or(1.0),
+        bfactor: field(60, 66).parse().unwrap_or(0.0),
+        element,
+        hetero: line.starts_with("HETATM"),
+    };
+    Ok(atom)
+}
+
+/// Split a data line in CIF format into tokens, respecting quotes.
+fn tokenize_cif_line(line: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '\'' || c == '"' {
+            chars.next();
+            let mut token = String::new();
+            while let Some(x) = chars.next() {
+                // a quote ends the token only if followed by whitespace
+                if x == c && chars.peek().map(|x| x.is_whitespace()).unwrap_or(true) {
+                    break;
+                }
+                token.push(x);
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&x) = chars.peek() {
+                if x.is_whitespace() {
+                    break;
+                }
+                token.push(x);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+#[test]
+fn test_read_pdb() -> Result<()> {
+    let pdb = "\
+MODEL        1
+ATOM      1  N   MET A   1      27.340  24.430   2.614  1.00  9.67           N
+ATOM      2  CA  MET A   1      26.266  25.413   2.842  1.00 10.38           C
+ATOM      3  C   MET A   1      26.913  26.639   3.531  1.00  9.62           C
+ATOM      4  O   MET A   1      27.886  26.463   4.263  1.00  9.62           O
+ATOM      5  N   GLN A   2      26.335  27.770   3.258  1.00  9.27           N
+ATOM      6  CA AGLN A   2      26.850  29.021   3.898  0.50  9.07           C
+ATOM      7  CA BGLN A   2      26.950  29.121   3.998  0.50  9.07           C
+ATOM      8  H   GLN A   2      25.520  27.782   2.681  1.00  9.00           H
+ATOM      9  CA  ILE B   3      26.235  30.058   3.000  1.00  8.29           C
+HETATM   10 CA    CA B 101      20.000  20.000  20.000  1.00 20.00          CA
+ENDMDL
+MODEL        2
+ATOM      1  CA  MET A   1      26.366  25.513   2.942  1.00 10.38           C
+ENDMDL
+";
+    let structure = Structure::from_pdb_str(pdb)?;
+    assert_eq!(structure.natoms(), 11);
+
+    let ca = structure.select(&Selection::default());
+    assert_eq!(ca.natoms(), 3);
+    assert_eq!(ca.residue_numbers(), vec![1, 2, 3]);
+    assert_eq!(ca.coords()[1], [26.850, 29.021, 3.898]);
+    assert_eq!(ca.masses(), vec![12.011; 3]);
+    let topology = ca.topology();
+    assert_eq!(topology.chains, vec![0, 0, 1]);
+    assert_eq!(topology.sequence_separation(0, 1), Some(1));
+
+    let selection = Selection {
+        atoms: AtomSelection::Backbone,
+        chains: vec!["A".into()],
+        residues: Some(1..=1),
+        ..Default::default()
+    };
+    assert_eq!(structure.select(&selection).natoms(), 4);
+
+    let selection = Selection {
+        atoms: AtomSelection::Heavy,
+        hetero: true,
+        ..Default::default()
+    };
+    let heavy = structure.select(&selection);
+    assert_eq!(heavy.natoms(), 8);
+    assert_eq!(heavy.residue_blocks(), vec![0, 0, 0, 0, 1, 1, 2, 3]);
+    assert_eq!(heavy.atoms()[7].mass(), 40.078);
+    let selection = Selection {
+        altloc: Some('B'),
+        ..Default::default()
+    };
+    assert_eq!(structure.select(&selection).coords()[1], [26.950, 29.121, 3.998]);
+
+    let selection = Selection {
+        model: 2,
+        ..Default::default()
+    };
+    assert_eq!(structure.select(&selection).coords(), vec![[26.366, 25.513, 2.942]]);
+
+    // one location for atoms with alternates other than A
+    let pdb = "\
+ATOM      1  CA  MET A   1      26.266  25.413   2.842  1.00 10.38           C
+ATOM      2  CA BGLN A   2      26.850  29.021   3.898  0.40  9.07           C
+ATOM      3  CA CGLN A   2      26.950  29.121   3.998  0.60  9.07           C
+ATOM      4  CA  ILE A   3      26.235  30.058   3.000  1.00  8.29           C
+";
+    let structure = Structure::from_pdb_str(pdb)?;
+    let ca = structure.select(&Selection::default());
+    assert_eq!(ca.residue_numbers(), vec![1, 2, 3]);
+    assert_eq!(ca.atoms()[1].altloc, Some('C'));
+    let selection = Selection {
+        altloc: Some('A'),
+        ..Default::default()
+    };
+    assert_eq!(structure.select(&selection).natoms(), 3);
+
+    let xyz = "2\ntitle\nC 0.0 0.0 0.0\nN 1.0 2.0 3.0\n";
+    let structure = Structure::from_xyz_str(xyz)?;
+    assert_eq!(structure.coords()[1], [1.0, 2.0, 3.0]);
+    assert_eq!(structure.masses(), vec![12.011, 14.007]);
+    assert!(Structure::from_xyz_str("3\ntitle\nC 0.0 0.0 0.0\n").is_err());
+
+    // CA atoms of modified residues in HETATM records
+    let pdb = "\
+ATOM      1  CA  MET A   1      26.266  25.413   2.842  1.00 10.38           C
+HETATM    2  N   MSE A   2      26.335  27.770   3.258  1.00  9.27           N
+HETATM    3  CA  MSE A   2      26.850  29.021   3.898  1.00  9.07           C
+HETATM    4 CA    CA A 101      20.000  20.000  20.000  1.00 20.00          CA
+";
+    let structure = Structure::from_pdb_str(pdb)?;
+    assert_eq!(structure.select(&Selection::default()).natoms(), 1);
+    let selection = Selection {
+        hetero: true,
+        ..Default::default()
+    };
+    assert_eq!(structure.select(&selection).residue_numbers(), vec![1, 2]);
+    let selection = Selection {
+        atoms: AtomSelection::Backbone,
+        hetero: true,
+        ..Default::default()
+    };
+    assert_eq!(structure.select(&selection).natoms(), 3);
+
+    let cif = "\
+data_test
+#
+loop_
+_atom_site.group_PDB
+_atom_site.id
+_atom_site.type_symbol
+_atom_site.label_atom_id
+_atom_site.label_alt_id
+_atom_site.label_comp_id
+_atom_site.label_asym_id
+_atom_site.label_seq_id
+_atom_site.pdbx_PDB_ins_code
+_atom_site.Cartn_x
+_atom_site.Cartn_y
+_atom_site.Cartn_z
+_atom_site.occupancy
+_atom_site.B_iso_or_equiv
+_atom_site.auth_seq_id
+_atom_site.auth_asym_id
+_atom_site.pdbx_PDB_model_num
+ATOM 1 N N . MET A 1 ? 27.340 24.430 2.614 1.00 9.67 1 A 1
+ATOM 2 C CA . MET A 1 ? 26.266 25.413 2.842 1.00 10.38 1 A 1
+ATOM 3 C \"C1'\" . MET A 1 ? 26.913 26.639 3.531 1.00 9.62 1 A 1
+ATOM 4 C CA A GLN A 2 ? 26.850 29.021 3.898 0.50 9.07 2 A 1
+ATOM 5 C CA B GLN A 2 ? 26.950 29.121 3.998 0.50 9.07 2 A 1
+#
+";
+    let structure = Structure::from_mmcif_str(cif)?;
+    assert_eq!(structure.natoms(), 5);
+    assert_eq!(structure.atoms()[2].name, "C1'");
+    let ca = structure.select(&Selection::default());
+    assert_eq!(ca.coords(), vec![[26.266, 25.413, 2.842], [26.850, 29.021, 3.898]]);
+    assert_eq!(ca.bfactors(), vec![10.38, 9.07]);
+
+    Ok(())
+}
+// 1e7b94d6 ends here
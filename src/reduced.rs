@@ -0,0 +1,160 @@
+// [[file:../enm.note::d8b41f6a][d8b41f6a]]
+use gut::prelude::*;
+use nalgebra::DMatrix;
+use vecfx::*;
+
+use crate::enm::{atom_masses, calculate_normal_modes};
+use crate::modes::{NetworkModel, NormalModes};
+use crate::AnisotropicNetworkModel;
+
+/// Effective Hessian of the first `ns` atoms in `hessian` with the rest
+/// atoms reduced out: H_ss - H_se H_ee^-1 H_es.
+pub(crate) fn schur_complement(hessian: &DMatrix<f64>, ns: usize) -> DMatrix<f64> {
+    let (ds, de) = (3 * ns, hessian.nrows() - 3 * ns);
+    let h_ss = hessian.slice((0, 0), (ds, ds));
+    if de == 0 {
+        return h_ss.into_owned();
+    }
+    let h_se = hessian.slice((0, ds), (ds, de));
+    let h_ee = hessian.slice((ds, ds), (de, de)).into_owned();
+    // H_ee is singular if some environment atoms are not connected to the system
+    let x = match h_ee.clone().cholesky() {
+        Some(chol) => chol.solve(&h_se.transpose()),
+        None => {
+            warn!("environment Hessian is singular, using pseudo-inverse.");
+            h_ee.pseudo_inverse(1E-10).expect("pseudo-inverse") * h_se.transpose()
+        }
+    };
+    h_ss - h_se * x
+}
+
+impl AnisotropicNetworkModel {
+    /// Build effective Hessian matrix (3S*3S) of `system` atoms in the
+    /// presence of `environment` atoms, by Schur complement of the full
+    /// Hessian matrix built by `build_hessian_matrix`. Atoms in neither
+    /// set are ignored.
+    ///
+    /// # References
+    ///
+    /// - Zheng, W.; Brooks, B. R. Biophysical Journal 2005, 89 (1), 167–178. <https://doi.org/10.1529/biophysj.105.063305>
+    /// - Ming, D.; Wall, M. E. Proteins 2005, 59 (4), 697–707. <https://doi.org/10.1002/prot.20440>
+    pub fn build_reduced_hessian_matrix<'a>(
+        &self,
+        coords: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+        system: &[usize],
+        environment: &[usize],
+    ) -> DMatrix<f64> {
+        let n = coords.len();
+        let masses = masses.into();
+        if let Some(masses) = masses {
+            assert_eq!(masses.len(), n, "invalid number of masses");
+        }
+        assert!(system.iter().chain(environment).all(|&i| i < n), "invalid atom index");
+        assert!(system.iter().chain(environment).all_unique(), "overlapping system and environment atoms");
+
+        // system atoms first, then environment atoms
+        let atoms = system.iter().chain(environment).copied().collect_vec();
+        let coords = atoms.iter().map(|&i| coords[i]).collect_vec();
+        let masses = masses.map(|m| atoms.iter().map(|&i| m[i]).collect_vec());
+        // bonded springs follow the reordered atoms
+        let mut model = self.clone();
+        if let Some(bonded) = model.bonded.as_mut() {
+            assert_eq!(bonded.topology.natoms(), n, "invalid number of atoms in topology");
+            bonded.topology = bonded.topology.subset(&atoms);
+        }
+        let hessian = model.build_hessian_matrix(&coords, masses.as_deref());
+        schur_complement(&hessian, system.len())
+    }
+
+    /// Calculates normal modes of `system` atoms in the presence of
+    /// `environment` atoms using the effective Hessian matrix from
+    /// `build_reduced_hessian_matrix`. The modes are ordered as atoms
+    /// in `system`.
+    pub fn reduced_normal_modes<'a>(
+        &self,
+        coords: &[[f64; 3]],
+        masses: impl Into<Option<&'a [f64]>>,
+        system: &[usize],
+        environment: &[usize],
+    ) -> NormalModes {
+        let masses = masses.into();
+        let hessian = self.build_reduced_hessian_matrix(coords, masses, system, environment);
+        let modes = calculate_normal_modes(hessian, self.zero_tolerance);
+        let masses = self.mass_weighted.then(|| {
+            let masses = atom_masses(coords.len(), masses);
+            system.iter().map(|&i| masses[i]).collect()
+        });
+        NormalModes::new(NetworkModel::Anisotropic(self.clone()), system.len(), masses, modes)
+    }
+}
+
+#[test]
+fn test_reduced_hessian() {
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    let anm = AnisotropicNetworkModel::default();
+    let system = [0, 2, 4, 6];
+    let environment = [1, 3, 5, 7];
+
+    // without environment, the same as the model of system atoms only
+    let hessian = anm.build_reduced_hessian_matrix(&coords, None, &system, &[]);
+    let sub = system.iter().map(|&i| coords[i]).collect_vec();
+    assert_relative_eq!(hessian, anm.build_hessian_matrix(&sub, None), epsilon = 1E-10);
+
+    // environment keeps rigid-body motions of the system
+    let modes = anm.reduced_normal_modes(&coords, None, &system, &environment);
+    assert_eq!(modes.natoms(), 4);
+    assert_eq!(modes.nmodes(), 3 * 4 - 6);
+
+    // relaxation of environment softens the system: S <= H_ss
+    let full = anm.build_hessian_matrix(&coords, None);
+    let reduced = anm.build_reduced_hessian_matrix(&coords, None, &system, &environment);
+    let h_ss = anm.build_reduced_hessian_matrix(&coords, None, &[0, 2, 4, 6, 1, 3, 5, 7], &[]);
+    let h_ss = h_ss.slice((0, 0), (12, 12)).into_owned();
+    let eigenvalues = |h: DMatrix<f64>| h.symmetric_eigen().eigenvalues.iter().copied().sorted_by_key(|x| OrderedFloat(*x)).collect_vec();
+    for (a, b) in eigenvalues(reduced.clone()).iter().zip(eigenvalues(h_ss)) {
+        assert!(*a <= b + 1E-8);
+    }
+
+    // holding the environment fixed gives higher energy than relaxing it
+    let (value, xs) = modes.mode(0);
+    let xs = nalgebra::DVector::from_column_slice(xs);
+    assert_relative_eq!(xs.dot(&(&reduced * &xs)), value, epsilon = 1E-8);
+    let energy = |x: &nalgebra::DVector<f64>| x.dot(&(&full * x));
+    let mut x = nalgebra::DVector::zeros(24);
+    for (k, &i) in system.iter().enumerate() {
+        for d in 0..3 {
+            x[3 * i + d] = xs[3 * k + d];
+        }
+    }
+    assert!(energy(&x) >= value - 1E-8);
+
+    // bonded springs between reordered atoms
+    use crate::spring::{BondedSprings, Topology};
+    let bonded = BondedSprings {
+        topology: Topology {
+            chains: vec![0; 8],
+            residues: (1..=8).collect(),
+        },
+        gammas: vec![10.0],
+    };
+    let anm = AnisotropicNetworkModel {
+        bonded: Some(bonded),
+        ..Default::default()
+    };
+    let full = anm.build_hessian_matrix(&coords, None);
+    let atoms = [7, 5, 3, 1, 6, 4, 2];
+    let hessian = anm.build_reduced_hessian_matrix(&coords, None, &atoms, &[]);
+    for (a, &i) in atoms.iter().enumerate() {
+        for (b, &j) in atoms.iter().enumerate().filter(|(b, _)| *b != a) {
+            let block = hessian.fixed_slice::<3, 3>(3 * a, 3 * b).into_owned();
+            assert_relative_eq!(block, full.fixed_slice::<3, 3>(3 * i, 3 * j).into_owned(), epsilon = 1E-10);
+        }
+    }
+    let modes = anm.reduced_normal_modes(&coords, None, &system, &environment[..3]);
+    assert_eq!(modes.nmodes(), 3 * 4 - 6);
+}
+// d8b41f6a ends here
@@ -0,0 +1,161 @@
+// [[file:../enm.note::5ad3f0c8][5ad3f0c8]]
+use gut::prelude::*;
+use nalgebra::DMatrix;
+use vecfx::*;
+
+use crate::enm::atom_masses;
+use crate::lobpcg::orthonormalize;
+use crate::neighbor::Contact;
+use crate::{AnisotropicNetworkModel, GaussianNetworkModel};
+
+/// Partition `natoms` atoms into connected components of the network
+/// defined by `contacts`. Each component is a list of sorted atom
+/// indices, and components are ordered by their first atom.
+pub fn connected_components(natoms: usize, contacts: &[Contact]) -> Vec<Vec<usize>> {
+    // union-find with path halving
+    let mut parent: Vec<usize> = (0..natoms).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for c in contacts {
+        let (ri, rj) = (find(&mut parent, c.i), find(&mut parent, c.j));
+        if ri != rj {
+            parent[ri.max(rj)] = ri.min(rj);
+        }
+    }
+
+    let mut components: Vec<Vec<usize>> = vec![];
+    let mut index_of_root = vec![usize::MAX; natoms];
+    for i in 0..natoms {
+        let root = find(&mut parent, i);
+        if index_of_root[root] == usize::MAX {
+            index_of_root[root] = components.len();
+            components.push(vec![]);
+        }
+        components[index_of_root[root]].push(i);
+    }
+    components
+}
+
+/// Returns orthonormal translational and rotational vectors (3N*r) for
+/// each connected component in `components` of atoms at `coords`. A
+/// single atom has 3 rigid-body modes, a linear component has 5 and
+/// others have 6. Vectors are weighted by sqrt(m) when `masses` are
+/// provided.
+pub fn rigid_body_vectors(coords: &[[f64; 3]], masses: Option<&[f64]>, components: &[Vec<usize>]) -> DMatrix<f64> {
+    let n = coords.len();
+    let mut basis = DMatrix::zeros(3 * n, 0);
+    for atoms in components {
+        let center: Vector3f = atoms.iter().map(|&i| Vector3f::from(coords[i])).sum::<Vector3f>() / atoms.len() as f64;
+        let mut vectors = DMatrix::zeros(3 * n, 6);
+        for &i in atoms {
+            let w = masses.map(|m| m[i].sqrt()).unwrap_or(1.0);
+            let r = Vector3f::from(coords[i]) - center;
+            for k in 0..3 {
+                // translation along axis k
+                vectors[(3 * i + k, k)] = w;
+                // rotation around axis k: e_k x r
+                let mut axis = Vector3f::zeros();
+                axis[k] = 1.0;
+                let v = axis.cross(&r) * w;
+                for d in 0..3 {
+                    vectors[(3 * i + d, 3 + k)] = v[d];
+                }
+            }
+        }
+        let vectors = orthonormalize(&basis, vectors);
+        basis = DMatrix::from_columns(&basis.column_iter().chain(vectors.column_iter()).collect_vec());
+    }
+    basis
+}
+
+impl AnisotropicNetworkModel {
+    /// Returns the connected components of the network for Cartesian
+    /// `coords`. Each component contributes its own rigid-body modes.
+    pub fn connected_components(&self, coords: &[[f64; 3]]) -> Vec<Vec<usize>> {
+        connected_components(coords.len(), &self.contacts(coords))
+    }
+
+    /// Returns orthonormal vectors (3N*r) spanning the null space of
+    /// the Hessian matrix, that is, the translations and rotations of
+    /// each connected component. The vectors are mass weighted when
+    /// `mass_weighted` is true, consistent with `build_hessian_matrix`.
+    pub fn rigid_body_vectors<'a>(&self, coords: &[[f64; 3]], masses: impl Into<Option<&'a [f64]>>) -> DMatrix<f64> {
+        let components = self.connected_components(coords);
+        if self.mass_weighted {
+            let masses = atom_masses(coords.len(), masses.into());
+            rigid_body_vectors(coords, Some(&masses), &components)
+        } else {
+            rigid_body_vectors(coords, None, &components)
+        }
+    }
+}
+
+impl GaussianNetworkModel {
+    /// Returns the connected components of the network for Cartesian
+    /// `coords`. There is one zero mode for each component.
+    pub fn connected_components(&self, coords: &[[f64; 3]]) -> Vec<Vec<usize>> {
+        connected_components(coords.len(), &self.contacts(coords))
+    }
+}
+
+#[test]
+fn test_rigid_body_modes() {
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    // two copies far apart, one isolated atom and a linear dimer
+    let mut system = coords.to_vec();
+    system.extend(coords.iter().map(|[x, y, z]| [x + 50.0, *y, *z]));
+    system.push([0.0, 50.0, 0.0]);
+    system.push([0.0, -50.0, 0.0]);
+    system.push([0.0, -51.0, 0.0]);
+
+    let anm = AnisotropicNetworkModel::default();
+    let components = anm.connected_components(&system);
+    assert_eq!(components.len(), 4);
+    assert_eq!(components[0], (0..8).collect_vec());
+
+    let rigid = anm.rigid_body_vectors(&system, None);
+    assert_eq!(rigid.ncols(), 6 + 6 + 3 + 5);
+    let hessian = anm.build_hessian_matrix(&system, None);
+    assert_relative_eq!((&hessian * &rigid).norm(), 0.0, epsilon = 1E-8);
+
+    let modes = anm.calculate_normal_modes(hessian);
+    assert_eq!(modes.len(), 3 * system.len() - rigid.ncols());
+    let modes_ref = anm.calculate_normal_modes(anm.build_hessian_matrix(&coords, None));
+    assert_relative_eq!(modes[0].0, modes_ref[0].0, epsilon = 1E-8);
+
+    let gnm = GaussianNetworkModel::default();
+    assert_eq!(gnm.connected_components(&system).len(), 4);
+    let modes = gnm.calculate_normal_modes(gnm.build_kirchhoff_matrix(&system));
+    assert_eq!(modes.len(), system.len() - 4);
+
+    // zero modes do not depend on the scale of force constants
+    for gamma in [1E-7, 1E7] {
+        let anm = AnisotropicNetworkModel {
+            gamma,
+            ..Default::default()
+        };
+        let modes = anm.calculate_normal_modes(anm.build_hessian_matrix(&coords, None));
+        assert_eq!(modes.len(), 3 * coords.len() - 6);
+        assert_relative_eq!(modes[0].0 / gamma, modes_ref[0].0, max_relative = 1E-6);
+        let modes = anm.calculate_lowest_modes(&anm.build_sparse_hessian_matrix(&coords, None), 3, &Default::default()).unwrap();
+        assert_relative_eq!(modes[0].0 / gamma, modes_ref[0].0, max_relative = 1E-6);
+        let modes = anm.calculate_normal_modes(anm.build_hessian_matrix(&system, None));
+        assert_eq!(modes.len(), 3 * system.len() - rigid.ncols());
+
+        let gnm = GaussianNetworkModel {
+            gamma,
+            ..Default::default()
+        };
+        let modes = gnm.calculate_normal_modes(gnm.build_kirchhoff_matrix(&system));
+        assert_eq!(modes.len(), system.len() - 4);
+    }
+}
+// 5ad3f0c8 ends here
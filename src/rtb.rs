@@ -0,0 +1,146 @@
+// [[file:../enm.note::a3c95e0f][a3c95e0f]]
+use std::collections::BTreeMap;
+
+use gut::prelude::*;
+use nalgebra::DMatrix;
+use vecfx::*;
+
+use crate::enm::{atom_masses, calculate_normal_modes};
+use crate::modes::{NetworkModel, NormalModes};
+use crate::rigid::rigid_body_vectors;
+use crate::AnisotropicNetworkModel;
+
+/// Rigid-body projection of atoms partitioned into blocks.
+struct RigidBlocks {
+    /// atom indices of each block
+    members: Vec<Vec<usize>>,
+    /// orthonormal rigid-body vectors of each block (3n*r for n atoms)
+    vectors: Vec<DMatrix<f64>>,
+    /// offset of each block in the reduced space
+    offsets: Vec<usize>,
+    /// block index and local atom index for each atom
+    locations: Vec<(usize, usize)>,
+}
+
+impl RigidBlocks {
+    fn new(coords: &[[f64; 3]], masses: Option<&[f64]>, blocks: &[usize]) -> Self {
+        assert_eq!(blocks.len(), coords.len(), "invalid number of block indices");
+        let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (i, &b) in blocks.iter().enumerate() {
+            groups.entry(b).or_default().push(i);
+        }
+        let members = groups.into_values().collect_vec();
+
+        let mut vectors = vec![];
+        let mut offsets = vec![];
+        let mut locations = vec![(0, 0); coords.len()];
+        let mut dim = 0;
+        for (b, atoms) in members.iter().enumerate() {
+            let local_coords = atoms.iter().map(|&i| coords[i]).collect_vec();
+            let local_masses = masses.map(|m| atoms.iter().map(|&i| m[i]).collect_vec());
+            let all = vec![(0..atoms.len()).collect_vec()];
+            let v = rigid_body_vectors(&local_coords, local_masses.as_deref(), &all);
+            offsets.push(dim);
+            dim += v.ncols();
+            vectors.push(v);
+            for (k, &i) in atoms.iter().enumerate() {
+                locations[i] = (b, k);
+            }
+        }
+
+        Self {
+            members,
+            vectors,
+            offsets,
+            locations,
+        }
+    }
+
+    /// The dimension of the reduced space.
+    fn dim(&self) -> usize {
+        self.offsets.last().copied().unwrap_or(0) + self.vectors.last().map(|v| v.ncols()).unwrap_or(0)
+    }
+
+    /// Rows of projection matrix for atom `i` (3*r).
+    fn projection(&self, i: usize) -> (usize, DMatrix<f64>) {
+        let (b, k) = self.locations[i];
+        let p = self.vectors[b].rows(3 * k, 3).into_owned();
+        (self.offsets[b], p)
+    }
+}
+
+impl AnisotropicNetworkModel {
+    /// Calculates normal modes using Rotation-Translation Block (RTB)
+    /// approximation, with atoms partitioned into rigid blocks by block
+    /// index in `blocks`, e.g. residues or secondary structure
+    /// elements. The Hessian matrix is projected into the space of
+    /// rigid-body motions of blocks, diagonalized there, and the modes
+    /// are expanded back to atomic displacements.
+    ///
+    /// # References
+    ///
+    /// - Tama, F. et al. Proteins 2000, 41 (1), 1–7. <https://doi.org/10.1002/1097-0134(20001001)41:1<1::AID-PROT10>3.0.CO;2-P>
+    pub fn rtb_normal_modes<'a>(&self, coords: &[[f64; 3]], masses: impl Into<Option<&'a [f64]>>, blocks: &[usize]) -> NormalModes {
+        let n = coords.len();
+        let masses = masses.into();
+        let masses = self.mass_weighted.then(|| atom_masses(n, masses));
+        let hessian = self.build_sparse_hessian_matrix(coords, masses.as_deref());
+        let rigid = RigidBlocks::new(coords, masses.as_deref(), blocks);
+        debug!("RTB: {} atoms in {} blocks, reduced dimension {}", n, rigid.members.len(), rigid.dim());
+
+        // projected Hessian: P^T H P
+        let projections = (0..n).map(|i| rigid.projection(i)).collect_vec();
+        let mut reduced = DMatrix::zeros(rigid.dim(), rigid.dim());
+        for (i, j, block) in hessian.iter_blocks() {
+            let (oi, pi) = &projections[i];
+            let (oj, pj) = &projections[j];
+            let h = pi.transpose() * block * pj;
+            let mut sub = reduced.slice_mut((*oi, *oj), h.shape());
+            sub += h;
+        }
+
+        // expand the modes back to atomic displacements
+        let modes = calculate_normal_modes(reduced, self.zero_tolerance)
+            .into_iter()
+            .map(|(value, v)| {
+                let mut u = vec![0.0; 3 * n];
+                for (i, (o, p)) in projections.iter().enumerate() {
+                    let ui = p * nalgebra::DVector::from_column_slice(&v[*o..*o + p.ncols()]);
+                    u[3 * i..3 * i + 3].copy_from_slice(ui.as_slice());
+                }
+                (value, u)
+            })
+            .collect_vec();
+        NormalModes::new(NetworkModel::Anisotropic(self.clone()), n, masses, modes)
+    }
+}
+
+#[test]
+fn test_rtb() {
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    // one atom per block is equivalent to the full model
+    let anm = AnisotropicNetworkModel::default();
+    let modes = anm.rtb_normal_modes(&coords, None, &[0, 1, 2, 3, 4, 5, 6, 7]);
+    let modes_ref = anm.normal_modes(&coords, None);
+    assert_eq!(modes.nmodes(), modes_ref.nmodes());
+    for k in 0..modes.nmodes() {
+        assert_relative_eq!(modes.eigenvalues()[k], modes_ref.eigenvalues()[k], epsilon = 1E-8);
+    }
+
+    // two rigid blocks interact by 6 internal degrees of freedom
+    let anm = AnisotropicNetworkModel {
+        mass_weighted: true,
+        ..Default::default()
+    };
+    let modes = anm.rtb_normal_modes(&coords, None, &[0, 0, 0, 0, 1, 1, 1, 1]);
+    assert_eq!(modes.nmodes(), 6);
+    let v = modes.eigenvectors().column(0);
+    assert_relative_eq!(v.norm(), 1.0, epsilon = 1E-8);
+    // RTB eigenvalues are upper bounds of the exact ones
+    let modes_ref = anm.normal_modes(&coords, None);
+    assert!(modes.eigenvalues()[0] >= modes_ref.eigenvalues()[0] - 1E-8);
+}
+// a3c95e0f ends here
@@ -0,0 +1,176 @@
+// [[file:../enm.note::7c1e0b94][7c1e0b94]]
+use nalgebra::{DMatrix, Matrix3};
+use vecfx::*;
+
+use crate::enm::atom_masses;
+use crate::neighbor::Contact;
+use crate::AnisotropicNetworkModel;
+
+/// Sparse Hessian matrix (3N*3N) storing only nonzero 3x3
+/// super-elements in block compressed sparse row (BSR) layout.
+#[derive(Debug, Clone)]
+pub struct SparseHessian {
+    natoms: usize,
+    /// offsets into `indices` and `blocks` for each block row, of
+    /// length N+1
+    indptr: Vec<usize>,
+    /// block column indices, sorted within each block row
+    indices: Vec<usize>,
+    /// 3x3 super-elements
+    blocks: Vec<Matrix3<f64>>,
+}
+
+impl SparseHessian {
+    /// Assemble from 3x3 super-elements `triplets` in coordinate (COO)
+    /// format for `natoms` atoms. Duplicate entries are summed.
+    pub fn from_triplets(natoms: usize, mut triplets: Vec<(usize, usize, Matrix3<f64>)>) -> Self {
+        triplets.sort_by_key(|x| (x.0, x.1));
+
+        let mut indptr = vec![0; natoms + 1];
+        let mut indices: Vec<usize> = vec![];
+        let mut blocks: Vec<Matrix3<f64>> = vec![];
+        let mut last = None;
+        for (i, j, block) in triplets {
+            assert!(i < natoms && j < natoms, "invalid block index: ({i}, {j})");
+            if last == Some((i, j)) {
+                *blocks.last_mut().unwrap() += block;
+            } else {
+                indptr[i + 1] += 1;
+                indices.push(j);
+                blocks.push(block);
+                last = Some((i, j));
+            }
+        }
+        for i in 0..natoms {
+            indptr[i + 1] += indptr[i];
+        }
+
+        Self {
+            natoms,
+            indptr,
+            indices,
+            blocks,
+        }
+    }
+
+    /// The number of atoms N.
+    pub fn natoms(&self) -> usize {
+        self.natoms
+    }
+
+    /// The dimension of the matrix (3N).
+    pub fn dim(&self) -> usize {
+        3 * self.natoms
+    }
+
+    /// The number of stored 3x3 super-elements.
+    pub fn nblocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the 3x3 super-element for atom pair (`i`, `j`) if stored.
+    pub fn block(&self, i: usize, j: usize) -> Option<&Matrix3<f64>> {
+        let (start, end) = (self.indptr[i], self.indptr[i + 1]);
+        let k = self.indices[start..end].binary_search(&j).ok()?;
+        Some(&self.blocks[start + k])
+    }
+
+    /// Iterate over all stored super-elements as (i, j, block).
+    pub fn iter_blocks(&self) -> impl Iterator<Item = (usize, usize, &Matrix3<f64>)> + '_ {
+        (0..self.natoms).flat_map(move |i| {
+            let (start, end) = (self.indptr[i], self.indptr[i + 1]);
+            (start..end).map(move |k| (i, self.indices[k], &self.blocks[k]))
+        })
+    }
+
+    /// Matrix-vector product H*x for vector `x` of length 3N.
+    pub fn dot(&self, x: &[f64]) -> Vec<f64> {
+        assert_eq!(x.len(), self.dim(), "invalid vector size");
+        let mut y = vec![0.0; self.dim()];
+        for (i, j, block) in self.iter_blocks() {
+            let xj = Vector3f::from_column_slice(&x[3 * j..3 * j + 3]);
+            let yi = block * xj;
+            y[3 * i] += yi[0];
+            y[3 * i + 1] += yi[1];
+            y[3 * i + 2] += yi[2];
+        }
+        y
+    }
+
+    /// Convert to dense matrix for full diagonalization.
+    pub fn to_dense(&self) -> DMatrix<f64> {
+        let n = self.dim();
+        let mut hessian = DMatrix::zeros(n, n);
+        for (i, j, block) in self.iter_blocks() {
+            let mut sub = hessian.fixed_slice_mut::<3, 3>(i * 3, j * 3);
+            sub.copy_from(block);
+        }
+        hessian
+    }
+}
+
+impl From<&SparseHessian> for DMatrix<f64> {
+    fn from(hessian: &SparseHessian) -> Self {
+        hessian.to_dense()
+    }
+}
+
+impl AnisotropicNetworkModel {
+    /// Build sparse Hessian matrix (3N*3N) for Cartesian `coords` of N
+    /// atoms. Only the 3x3 super-elements of atom pairs within
+    /// `cutoff` are stored.
+    pub fn build_sparse_hessian_matrix<'a>(&self, coords: &[[f64; 3]], masses: impl Into<Option<&'a [f64]>>) -> SparseHessian {
+        let n = coords.len();
+        let masses = masses.into();
+        if let Some(masses) = masses {
+            assert_eq!(masses.len(), n, "invalid number of masses");
+        }
+
+        let mut diagonal = vec![Matrix3::zeros(); n];
+        let mut triplets = vec![];
+        for Contact { i, j, distance } in self.contacts(coords) {
+            let ri: Vector3f = coords[i].into();
+            let rj: Vector3f = coords[j].into();
+            let rij = rj - ri;
+            let gamma = self.force_constant(i, j, distance);
+            let super_element = -gamma / distance.powi(2) * rij * rij.transpose();
+            triplets.push((i, j, super_element));
+            triplets.push((j, i, super_element));
+            diagonal[i] -= super_element;
+            diagonal[j] -= super_element;
+        }
+        triplets.extend(diagonal.into_iter().enumerate().map(|(i, block)| (i, i, block)));
+
+        // mass weighted Hessian matrix: scale each super-element by 1/sqrt(mi*mj)
+        if self.mass_weighted {
+            let masses = atom_masses(n, masses);
+            for (i, j, block) in triplets.iter_mut() {
+                *block /= (masses[*i] * masses[*j]).sqrt();
+            }
+        }
+
+        SparseHessian::from_triplets(n, triplets)
+    }
+}
+
+#[test]
+fn test_sparse_hessian() {
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    let anm = AnisotropicNetworkModel {
+        cutoff: 2.5,
+        ..Default::default()
+    };
+    let dense = anm.build_hessian_matrix(&coords, None);
+    let sparse = anm.build_sparse_hessian_matrix(&coords, None);
+    assert!(sparse.nblocks() < coords.len().pow(2));
+    assert_relative_eq!(sparse.to_dense(), dense, epsilon = 1E-10);
+
+    let x: Vec<_> = (0..sparse.dim()).map(|i| (i as f64).sin()).collect();
+    let y = sparse.dot(&x);
+    let y_dense = &dense * nalgebra::DVector::from_column_slice(&x);
+    assert_relative_eq!(y.as_slice(), y_dense.as_slice(), epsilon = 1E-10);
+}
+// 7c1e0b94 ends here
@@ -0,0 +1,225 @@
+// [[file:../enm.note::62f0a9d4][62f0a9d4]]
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::neighbor::Contact;
+
+/// Force constant of springs in the elastic network as a function of
+/// atom pair distance.
+///
+/// # References
+///
+/// - Yang, L. et al. PNAS 2009, 106 (30), 12347–12352. <https://doi.org/10.1073/pnas.0902159106>
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpringConstant {
+    /// Uniform force constant `gamma` for all atom pairs within cutoff.
+    #[default]
+    #[serde(alias = "Uniform")]
+    Uniform,
+    /// Inverse power of distance `gamma / r^p` with exponent `p`.
+    #[serde(alias = "InversePower")]
+    InversePower(f64),
+    /// Exponential decay with distance `gamma * exp(-r / r0)` with
+    /// characteristic length `r0`.
+    #[serde(alias = "Exponential")]
+    Exponential(f64),
+    /// Parameter-free ANM: `gamma / r^2` for all atom pairs without
+    /// cutoff.
+    #[serde(alias = "ParameterFree")]
+    ParameterFree,
+    /// User-supplied force constant `k(i, j, r)` for atom pair (`i`,
+    /// `j`) separated by `r`. `gamma` is not applied. Cannot be
+    /// serialized.
+    #[serde(skip)]
+    Custom(Arc<dyn Fn(usize, usize, f64) -> f64 + Send + Sync>),
+}
+
+impl std::fmt::Debug for SpringConstant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Uniform => write!(f, "Uniform"),
+            Self::InversePower(p) => f.debug_tuple("InversePower").field(p).finish(),
+            Self::Exponential(r0) => f.debug_tuple("Exponential").field(r0).finish(),
+            Self::ParameterFree => write!(f, "ParameterFree"),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl SpringConstant {
+    /// Construct from user-supplied closure `k(i, j, r)`.
+    pub fn custom(f: impl Fn(usize, usize, f64) -> f64 + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(f))
+    }
+
+    /// Returns false if springs connect all atom pairs regardless of
+    /// cutoff.
+    pub fn uses_cutoff(&self) -> bool {
+        !matches!(self, Self::ParameterFree)
+    }
+
+    /// Force constant for atom pair (`i`, `j`) separated by `r`, with
+    /// `gamma` as the overall scaling factor.
+    pub fn force_constant(&self, gamma: f64, i: usize, j: usize, r: f64) -> f64 {
+        match self {
+            Self::Uniform => gamma,
+            Self::InversePower(p) => gamma / r.powf(*p),
+            Self::Exponential(r0) => gamma * (-r / r0).exp(),
+            Self::ParameterFree => gamma / r.powi(2),
+            Self::Custom(f) => f(i, j, r),
+        }
+    }
+}
+
+/// Chain topology of atoms, for identifying neighbors along the
+/// sequence.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Topology {
+    /// chain index of each atom
+    pub chains: Vec<usize>,
+    /// residue sequence number of each atom
+    pub residues: Vec<i32>,
+}
+
+impl Topology {
+    /// The number of atoms.
+    pub fn natoms(&self) -> usize {
+        self.residues.len()
+    }
+
+    /// Topology of the atoms at `indices`, in the same order.
+    pub fn subset(&self, indices: &[usize]) -> Self {
+        Self {
+            chains: indices.iter().map(|&i| self.chains[i]).collect(),
+            residues: indices.iter().map(|&i| self.residues[i]).collect(),
+        }
+    }
+
+    /// Sequence separation between atom `i` and atom `j`, or None if
+    /// they are in different chains.
+    pub fn sequence_separation(&self, i: usize, j: usize) -> Option<usize> {
+        (self.chains[i] == self.chains[j]).then(|| self.residues[i].abs_diff(self.residues[j]) as usize)
+    }
+}
+
+/// Stronger springs between sequence-bonded neighbors, as in the models
+/// of Hinsen or Kovacs.
+///
+/// # References
+///
+/// - Hinsen, K. et al. Chemical Physics 2000, 261 (1), 25–37. <https://doi.org/10.1016/S0301-0104(00)00222-6>
+/// - Kovacs, J. A. et al. Proteins 2004, 56 (4), 661–668. <https://doi.org/10.1002/prot.20151>
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BondedSprings {
+    pub topology: Topology,
+    /// Force constants for neighbors i±1, i±2, ... along the chain,
+    /// replacing the ones of nonbonded contacts.
+    pub gammas: Vec<f64>,
+}
+
+impl BondedSprings {
+    /// Force constant for atom pair (`i`, `j`) if they are bonded
+    /// neighbors.
+    pub fn force_constant(&self, i: usize, j: usize) -> Option<f64> {
+        let s = self.topology.sequence_separation(i, j)?;
+        (s > 0).then(|| self.gammas.get(s - 1).copied()).flatten()
+    }
+
+    /// Returns all bonded atom pairs for Cartesian `coords` regardless
+    /// of cutoff, sorted by (`i`, `j`).
+    pub fn contacts(&self, coords: &[[f64; 3]]) -> Vec<Contact> {
+        let topology = &self.topology;
+        assert_eq!(topology.natoms(), coords.len(), "invalid number of atoms in topology");
+
+        let mut residues: HashMap<(usize, i32), Vec<usize>> = HashMap::new();
+        for i in 0..coords.len() {
+            residues.entry((topology.chains[i], topology.residues[i])).or_default().push(i);
+        }
+        let mut contacts = vec![];
+        for i in 0..coords.len() {
+            let (chain, resseq) = (topology.chains[i], topology.residues[i]);
+            for s in 1..=self.gammas.len() as i32 {
+                for k in [resseq - s, resseq + s] {
+                    for &j in residues.get(&(chain, k)).into_iter().flatten().filter(|&&j| j < i) {
+                        let distance = (0..3).map(|d| (coords[i][d] - coords[j][d]).powi(2)).sum::<f64>().sqrt();
+                        contacts.push(Contact { i, j, distance });
+                    }
+                }
+            }
+        }
+        contacts.sort_by_key(|c| (c.i, c.j));
+        contacts
+    }
+}
+
+#[test]
+fn test_spring_constant() {
+    use crate::{AnisotropicNetworkModel, GaussianNetworkModel};
+    use approx::*;
+    use vecfx::*;
+
+    let coords = crate::enm::test_coords();
+
+    assert_relative_eq!(SpringConstant::InversePower(2.0).force_constant(2.0, 0, 1, 2.0), 0.5);
+    assert_relative_eq!(SpringConstant::Exponential(1.0).force_constant(1.0, 0, 1, 1.0), (-1f64).exp());
+
+    // custom closure reproduces uniform springs
+    let anm = AnisotropicNetworkModel::default();
+    let anm_custom = AnisotropicNetworkModel {
+        spring: SpringConstant::custom(|_, _, _| 1.0),
+        ..Default::default()
+    };
+    let hessian = anm.build_hessian_matrix(&coords, None);
+    assert_relative_eq!(anm_custom.build_hessian_matrix(&coords, None), hessian, epsilon = 1E-10);
+
+    // pfANM ignores cutoff
+    let pfanm = AnisotropicNetworkModel {
+        cutoff: 2.5,
+        spring: SpringConstant::ParameterFree,
+        ..Default::default()
+    };
+    assert_eq!(pfanm.contacts(&coords).len(), 8 * 7 / 2);
+    let hessian = pfanm.build_hessian_matrix(&coords, None);
+    let sparse = pfanm.build_sparse_hessian_matrix(&coords, None);
+    assert_relative_eq!(sparse.to_dense(), hessian, epsilon = 1E-10);
+    assert_eq!(pfanm.calculate_normal_modes(hessian).len(), 18);
+
+    let gnm = GaussianNetworkModel {
+        spring: SpringConstant::InversePower(2.0),
+        ..Default::default()
+    };
+    let kirchhoff = gnm.build_kirchhoff_matrix(&coords);
+    let r01 = (0..3).map(|k| (coords[0][k] - coords[1][k]).powi(2)).sum::<f64>();
+    assert_relative_eq!(kirchhoff[(0, 1)], -1.0 / r01, epsilon = 1E-10);
+
+    // bonded neighbors along two chains
+    let topology = Topology {
+        chains: vec![0, 0, 0, 0, 1, 1, 1, 1],
+        residues: vec![1, 2, 3, 4, 1, 2, 3, 4],
+    };
+    assert_eq!(topology.sequence_separation(0, 2), Some(2));
+    assert_eq!(topology.sequence_separation(0, 4), None);
+    let bonded = BondedSprings {
+        topology,
+        gammas: vec![10.0, 5.0],
+    };
+    assert_eq!(bonded.contacts(&coords).len(), 2 * (3 + 2));
+    let anm = AnisotropicNetworkModel {
+        cutoff: 2.5,
+        bonded: Some(bonded),
+        ..Default::default()
+    };
+    let contacts = anm.contacts(&coords);
+    assert!(contacts.iter().any(|c| (c.i, c.j) == (3, 1)));
+    assert!(contacts.windows(2).all(|w| (w[0].i, w[0].j) < (w[1].i, w[1].j)));
+    let hessian = anm.build_hessian_matrix(&coords, None);
+    let rij = Vector3f::from(coords[1]) - Vector3f::from(coords[0]);
+    let block = -10.0 / rij.norm_squared() * rij * rij.transpose();
+    assert_relative_eq!(hessian.fixed_slice::<3, 3>(3, 0).into_owned(), block, epsilon = 1E-10);
+    let sparse = anm.build_sparse_hessian_matrix(&coords, None);
+    assert_relative_eq!(sparse.to_dense(), hessian, epsilon = 1E-10);
+}
+// 62f0a9d4 ends here
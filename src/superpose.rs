@@ -0,0 +1,120 @@
+// [[file:../enm.note::9e4a21c6][9e4a21c6]]
+use nalgebra::Matrix3;
+use vecfx::*;
+
+/// Optimal rigid-body superposition of a mobile structure onto a
+/// reference structure.
+#[derive(Debug, Clone)]
+pub struct Superposition {
+    /// Rotation matrix applied to centered mobile coordinates
+    pub rotation: Matrix3<f64>,
+    /// Weighted centroid of mobile coordinates
+    pub mobile_center: [f64; 3],
+    /// Weighted centroid of reference coordinates
+    pub reference_center: [f64; 3],
+    /// Weighted RMSD after superposition
+    pub rmsd: f64,
+}
+
+impl Superposition {
+    /// Move `coords` in the frame of the mobile structure onto the
+    /// reference structure.
+    pub fn apply(&self, coords: &[[f64; 3]]) -> Vec<[f64; 3]> {
+        let cm = Vector3f::from(self.mobile_center);
+        let cr = Vector3f::from(self.reference_center);
+        coords.iter().map(|p| (self.rotation * (Vector3f::from(*p) - cm) + cr).into()).collect()
+    }
+}
+
+fn weighted_center(coords: &[[f64; 3]], weights: Option<&[f64]>) -> Vector3f {
+    let w = |i: usize| weights.map(|w| w[i]).unwrap_or(1.0);
+    let wsum: f64 = (0..coords.len()).map(w).sum();
+    coords.iter().enumerate().fold(Vector3f::zeros(), |a, (i, p)| a + w(i) * Vector3f::from(*p)) / wsum
+}
+
+/// Optimal superposition of `mobile` onto `reference` with identical
+/// atom ordering by Kabsch algorithm, minimizing RMSD weighted by
+/// optional atom `weights`, e.g. masses.
+///
+/// # References
+///
+/// - Kabsch, W. Acta Cryst. A 1976, 32 (5), 922–923. <https://doi.org/10.1107/S0567739476001873>
+pub fn superpose(mobile: &[[f64; 3]], reference: &[[f64; 3]], weights: Option<&[f64]>) -> Superposition {
+    let n = reference.len();
+    assert_eq!(mobile.len(), n, "structures differ in number of atoms");
+    assert!(n > 0, "empty structures");
+    if let Some(weights) = weights {
+        assert_eq!(weights.len(), n, "invalid number of weights");
+    }
+    let cm = weighted_center(mobile, weights);
+    let cr = weighted_center(reference, weights);
+
+    let mut cov = Matrix3::zeros();
+    for i in 0..n {
+        let w = weights.map(|w| w[i]).unwrap_or(1.0);
+        cov += w * (Vector3f::from(mobile[i]) - cm) * (Vector3f::from(reference[i]) - cr).transpose();
+    }
+    let svd = cov.svd(true, true);
+    let (u, vt) = (svd.u.expect("svd u"), svd.v_t.expect("svd v_t"));
+    // avoid improper rotation
+    let d = (vt.transpose() * u.transpose()).determinant().signum();
+    let rotation = vt.transpose() * Matrix3::from_diagonal(&Vector3f::new(1.0, 1.0, d)) * u.transpose();
+
+    let mut superposition = Superposition {
+        rotation,
+        mobile_center: cm.into(),
+        reference_center: cr.into(),
+        rmsd: 0.0,
+    };
+    superposition.rmsd = rmsd(&superposition.apply(mobile), reference, weights);
+    superposition
+}
+
+/// Per-atom deviations between `a` and `b` without superposition.
+pub fn deviations(a: &[[f64; 3]], b: &[[f64; 3]]) -> Vec<f64> {
+    assert_eq!(a.len(), b.len(), "structures differ in number of atoms");
+    a.iter()
+        .zip(b)
+        .map(|(p, q)| (0..3).map(|k| (p[k] - q[k]).powi(2)).sum::<f64>().sqrt())
+        .collect()
+}
+
+/// RMSD between `a` and `b` without superposition, weighted by optional
+/// atom `weights`.
+pub fn rmsd(a: &[[f64; 3]], b: &[[f64; 3]], weights: Option<&[f64]>) -> f64 {
+    let d = deviations(a, b);
+    if let Some(weights) = weights {
+        assert_eq!(weights.len(), d.len(), "invalid number of weights");
+    }
+    let w = |i: usize| weights.map(|w| w[i]).unwrap_or(1.0);
+    let wsum: f64 = (0..d.len()).map(w).sum();
+    (d.iter().enumerate().map(|(i, x)| w(i) * x * x).sum::<f64>() / wsum).sqrt()
+}
+
+#[test]
+fn test_superpose() {
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    // rotated and translated copy
+    let (c, s) = (0.7f64.cos(), 0.7f64.sin());
+    let mobile: Vec<[f64; 3]> = coords.iter().map(|p| [p[0] + 3.0, c * p[1] - s * p[2], s * p[1] + c * p[2] - 1.0]).collect();
+    assert!(rmsd(&mobile, &coords, None) > 1.0);
+
+    let sp = superpose(&mobile, &coords, None);
+    assert_relative_eq!(sp.rmsd, 0.0, epsilon = 1E-8);
+    assert_relative_eq!(sp.rotation.determinant(), 1.0, epsilon = 1E-8);
+    let aligned = sp.apply(&mobile);
+    assert!(deviations(&aligned, &coords).iter().all(|&x| x < 1E-8));
+
+    // weights
+    let masses = [12.0, 14.0, 16.0, 12.0, 12.0, 14.0, 16.0, 32.0];
+    let sp = superpose(&mobile, &coords, Some(&masses));
+    assert_relative_eq!(sp.rmsd, 0.0, epsilon = 1E-8);
+    let mut moved = coords.to_vec();
+    moved[7][0] += 1.0;
+    assert_relative_eq!(rmsd(&moved, &coords, None), (1.0f64 / 8.0).sqrt(), epsilon = 1E-10);
+    assert_relative_eq!(rmsd(&moved, &coords, Some(&masses)), (32.0f64 / 128.0).sqrt(), epsilon = 1E-10);
+}
+// 9e4a21c6 ends here
@@ -0,0 +1,168 @@
+// [[file:../enm.note::2f8c5d13][2f8c5d13]]
+use std::fmt::Write as _;
+
+use gut::prelude::*;
+
+use crate::modes::NormalModes;
+use crate::pdb::AtomRecord;
+
+/// Amplitude of atomic displacements along a mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Amplitude {
+    /// RMSD in Å of the most displaced frame from the reference
+    /// structure.
+    Rmsd(f64),
+    /// Thermal amplitude sqrt(kT/λ) of the mode at temperature `kT` in
+    /// the same energy units as the force constants.
+    Thermal(f64),
+}
+
+/// A series of coordinate frames, e.g. oscillation along a normal mode.
+#[derive(Debug, Clone, Default)]
+pub struct Trajectory {
+    pub frames: Vec<Vec<[f64; 3]>>,
+}
+
+impl NormalModes {
+    /// Generate `nframes` frames displaced sinusoidally from `coords`
+    /// along mode `k`, covering one period of oscillation.
+    pub fn mode_trajectory(&self, coords: &[[f64; 3]], k: usize, amplitude: Amplitude, nframes: usize) -> Trajectory {
+        assert_eq!(coords.len(), self.natoms(), "invalid number of atoms");
+        let d = self.displacements(k);
+        let scale = match amplitude {
+            Amplitude::Rmsd(rmsd) => {
+                let norm = d.iter().flatten().map(|x| x * x).sum::<f64>().sqrt();
+                rmsd * (self.natoms() as f64).sqrt() / norm
+            }
+            Amplitude::Thermal(kt) => (kt / self.eigenvalues()[k]).sqrt(),
+        };
+        let frames = (0..nframes)
+            .map(|t| {
+                let s = scale * (2.0 * std::f64::consts::PI * t as f64 / nframes as f64).sin();
+                coords
+                    .iter()
+                    .zip(&d)
+                    .map(|(p, d)| [p[0] + s * d[0], p[1] + s * d[1], p[2] + s * d[2]])
+                    .collect()
+            })
+            .collect();
+        Trajectory { frames }
+    }
+}
+
+impl Trajectory {
+    /// Format as multi-model PDB. Atom records are taken from `atoms`
+    /// with positions replaced, or CA atoms of glycine residues are
+    /// written if None.
+    pub fn to_pdb_string(&self, atoms: Option<&[AtomRecord]>) -> String {
+        let mut s = String::new();
+        for (m, frame) in self.frames.iter().enumerate() {
+            if let Some(atoms) = atoms {
+                assert_eq!(atoms.len(), frame.len(), "invalid number of atom records");
+            }
+            writeln!(s, "MODEL     {:>4}", m + 1).unwrap();
+            for (i, p) in frame.iter().enumerate() {
+                let atom = match atoms {
+                    Some(atoms) => AtomRecord {
+                        position: *p,
+                        ..atoms[i].clone()
+                    },
+                    None => placeholder_atom(i, *p),
+                };
+                writeln!(s, "{}", atom.to_pdb_line()).unwrap();
+            }
+            writeln!(s, "ENDMDL").unwrap();
+        }
+        writeln!(s, "END").unwrap();
+        s
+    }
+
+    /// Format as multi-frame XYZ with element symbols from `elements`,
+    /// or Carbon if None.
+    pub fn to_xyz_string(&self, elements: Option<&[String]>) -> String {
+        let mut s = String::new();
+        for (m, frame) in self.frames.iter().enumerate() {
+            if let Some(elements) = elements {
+                assert_eq!(elements.len(), frame.len(), "invalid number of elements");
+            }
+            writeln!(s, "{}", frame.len()).unwrap();
+            writeln!(s, "frame {}", m + 1).unwrap();
+            for (i, [x, y, z]) in frame.iter().enumerate() {
+                let symbol = elements.map(|e| e[i].as_str()).unwrap_or("C");
+                writeln!(s, "{symbol:<2} {x:12.5} {y:12.5} {z:12.5}").unwrap();
+            }
+        }
+        s
+    }
+
+    /// Write as multi-model PDB file at `path`. See also `to_pdb_string`.
+    pub fn write_pdb(&self, path: impl AsRef<Path>, atoms: Option<&[AtomRecord]>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_pdb_string(atoms)).with_context(|| format!("failed to write {path:?}"))
+    }
+
+    /// Write as multi-frame XYZ file at `path`. See also `to_xyz_string`.
+    pub fn write_xyz(&self, path: impl AsRef<Path>, elements: Option<&[String]>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_xyz_string(elements)).with_context(|| format!("failed to write {path:?}"))
+    }
+}
+
+/// CA atom of a glycine residue for atom `i` without atom records.
+fn placeholder_atom(i: usize, position: [f64; 3]) -> AtomRecord {
+    AtomRecord {
+        model: 1,
+        serial: i + 1,
+        name: "CA".into(),
+        altloc: None,
+        resname: "GLY".into(),
+        chain: "A".into(),
+        resseq: (i % 10000) as i32 + 1,
+        icode: None,
+        position,
+        occupancy: 1.0,
+        bfactor: 0.0,
+        element: "C".into(),
+        hetero: false,
+    }
+}
+
+#[test]
+fn test_mode_trajectory() -> Result<()> {
+    use crate::pdb::{Selection, Structure};
+    use crate::superpose::rmsd;
+    use crate::AnisotropicNetworkModel;
+    use approx::*;
+
+    let coords = crate::enm::test_coords();
+
+    let anm = AnisotropicNetworkModel::default();
+    let modes = anm.normal_modes(&coords, None);
+    let traj = modes.mode_trajectory(&coords, 0, Amplitude::Rmsd(1.5), 8);
+    assert_eq!(traj.frames.len(), 8);
+    assert_eq!(traj.frames[0], coords.to_vec());
+    assert_relative_eq!(rmsd(&traj.frames[2], &coords, None), 1.5, epsilon = 1E-8);
+    assert_relative_eq!(rmsd(&traj.frames[6], &coords, None), 1.5, epsilon = 1E-8);
+
+    // thermal amplitude of unit eigenvector
+    let traj_kt = modes.mode_trajectory(&coords, 0, Amplitude::Thermal(1.0), 4);
+    let expected = (1.0 / modes.eigenvalues()[0] / 8.0).sqrt();
+    assert_relative_eq!(rmsd(&traj_kt.frames[1], &coords, None), expected, epsilon = 1E-8);
+
+    // read back written PDB models
+    let pdb = traj.to_pdb_string(None);
+    let structure = Structure::from_pdb_str(&pdb)?;
+    assert_eq!(structure.natoms(), 8 * 8);
+    let selection = Selection {
+        model: 3,
+        ..Default::default()
+    };
+    let frame = structure.select(&selection).coords();
+    assert_relative_eq!(rmsd(&frame, &traj.frames[2], None), 0.0, epsilon = 1E-3);
+
+    let xyz = traj.to_xyz_string(None);
+    assert_eq!(xyz.lines().count(), 8 * (8 + 2));
+
+    Ok(())
+}
+// 2f8c5d13 ends here